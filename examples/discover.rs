@@ -0,0 +1,24 @@
+use ql_label::Printer;
+
+fn main() {
+    env_logger::init();
+
+    match Printer::discover() {
+        Ok(devices) => {
+            if devices.is_empty() {
+                println!("No printer found");
+            }
+            for device in devices {
+                println!(
+                    "Bus {:03} Device {:03}: {:?} serial={} product={}",
+                    device.bus_number,
+                    device.address,
+                    device.model,
+                    device.serial,
+                    device.product.unwrap_or_default()
+                );
+            }
+        }
+        Err(err) => println!("Error {:#?}", err),
+    }
+}
@@ -0,0 +1,37 @@
+use ql_label::{decode, lint, Model};
+use std::str::FromStr;
+
+fn main() {
+    env_logger::init();
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} <model, e.g. QL-800> <job.bin>", args[0]);
+        std::process::exit(1);
+    }
+
+    let model = match Model::from_str(&args[1]) {
+        Ok(model) => model,
+        Err(_) => {
+            eprintln!("Unknown model {}", args[1]);
+            std::process::exit(1);
+        }
+    };
+    let bytes = std::fs::read(&args[2]).expect("Failed to read raster file");
+
+    match decode(&bytes) {
+        Ok(commands) => {
+            for command in &commands {
+                log::debug!("{:?}", command);
+            }
+            let lints = lint(&commands, model);
+            if lints.is_empty() {
+                println!("{} commands, no problem found", commands.len());
+            }
+            for lint in lints {
+                println!("{}", lint);
+            }
+        }
+        Err(err) => println!("Error {}", err),
+    }
+}
@@ -121,7 +121,7 @@ fn main() {
 fn create_test_pattern() -> TwoColorMatrix {
     let width = ql_label::NORMAL_PRINTER_WIDTH;
     let height = 300;
-    let byte_width = (width + 7) / 8;
+    let byte_width = width.div_ceil(8);
 
     let mut black_matrix = vec![vec![0u8; byte_width as usize]; height];
     let mut red_matrix = vec![vec![0u8; byte_width as usize]; height];
@@ -32,7 +32,10 @@ fn main() {
 
     match Printer::new(config) {
         Ok(printer) => match printer.check_status() {
-            Ok(status) => println!("{:#?}", status),
+            Ok(status) => {
+                println!("{}", status);
+                println!("{:#?}", status);
+            }
             Err(err) => println!("Error {:#?}", err),
         },
         Err(err) => panic!("Invalid configuration settings: {}", err),
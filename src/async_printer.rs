@@ -0,0 +1,285 @@
+//! Printer API for tokio applications.
+//!
+//! `AsyncPrinter` moves a `Printer` to a dedicated I/O thread, so the blocking
+//! USB and network calls and the status polling never run on a runtime
+//! thread. Requests are sent to the thread and awaited through channels, the
+//! encoding and status parsing are the ones of `Printer`.
+
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use futures_core::Stream;
+use log::debug;
+use tokio::sync::{mpsc as async_mpsc, oneshot};
+
+use crate::{
+    cancel::CancelToken, error::Error, printer::Printer, status::Status, transport::Transport,
+    utils::TwoColorMatrix, Matrix,
+};
+
+enum Request {
+    Print(Vec<Matrix>, oneshot::Sender<Result<(), Error>>),
+    PrintTwoColor(Vec<TwoColorMatrix>, oneshot::Sender<Result<(), Error>>),
+    CheckStatus(oneshot::Sender<Result<Status, Error>>),
+    Subscribe(Duration, async_mpsc::Sender<Result<Status, Error>>),
+}
+
+/// Asynchronous front end of a `Printer`, available with the `tokio` feature.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{AsyncPrinter, ContinuousType, Media, Model, SimulatedPrinter};
+/// # let media = Media::Continuous(ContinuousType::Continuous62);
+/// # let (_, printer) = SimulatedPrinter::connect(Model::QL820NWB, media);
+/// # let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// # runtime.block_on(async {
+/// let printer = AsyncPrinter::new(printer);
+///
+/// let status = printer.check_status().await?;
+/// println!("{}", status);
+///
+/// printer.print(vec![vec![vec![0xFF; 90]; 10]]).await?;
+/// # Ok::<(), ql_label::Error>(())
+/// # }).unwrap();
+/// ```
+pub struct AsyncPrinter {
+    requests: mpsc::Sender<Request>,
+    cancel: CancelToken,
+}
+
+impl AsyncPrinter {
+    /// Start the I/O thread owning `printer`.
+    ///
+    /// The thread stops once the `AsyncPrinter` and every status stream
+    /// are dropped.
+    pub fn new<T>(printer: Printer<T>) -> Self
+    where
+        T: Transport + Send + 'static,
+    {
+        let (requests, receiver) = mpsc::channel();
+        let cancel = printer.cancel_token();
+        thread::Builder::new()
+            .name("ql-label-io".to_string())
+            .spawn(move || Worker::new(printer).run(receiver))
+            .expect("failed to spawn printer I/O thread");
+
+        AsyncPrinter { requests, cancel }
+    }
+
+    /// Print single-color labels, see `Printer::print`.
+    pub async fn print(&self, images: impl IntoIterator<Item = Matrix>) -> Result<(), Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(Request::Print(images.into_iter().collect(), reply))?;
+        response.await.map_err(|_| Error::WorkerStopped)?
+    }
+
+    /// Print two-color labels, see `Printer::print_two_color`.
+    pub async fn print_two_color(
+        &self,
+        images: impl IntoIterator<Item = TwoColorMatrix>,
+    ) -> Result<(), Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(Request::PrintTwoColor(images.into_iter().collect(), reply))?;
+        response.await.map_err(|_| Error::WorkerStopped)?
+    }
+
+    /// Read the current printer status, see `Printer::check_status`.
+    ///
+    /// Waits for a running print job to finish first.
+    pub async fn check_status(&self) -> Result<Status, Error> {
+        let (reply, response) = oneshot::channel();
+        self.send(Request::CheckStatus(reply))?;
+        response.await.map_err(|_| Error::WorkerStopped)?
+    }
+
+    /// Poll the status every `interval` while the printer is idle.
+    ///
+    /// No status is polled while a print job runs, statuses not taken from
+    /// the stream in time are skipped.
+    pub fn status_stream(&self, interval: Duration) -> Result<StatusStream, Error> {
+        let (sender, receiver) = async_mpsc::channel(8);
+        self.send(Request::Subscribe(interval, sender))?;
+        Ok(StatusStream { receiver })
+    }
+
+    /// Token to cancel the running print job, see `Printer::cancel_token`.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    fn send(&self, request: Request) -> Result<(), Error> {
+        self.requests.send(request).map_err(|_| Error::WorkerStopped)
+    }
+}
+
+/// Statuses polled by the I/O thread, see `AsyncPrinter::status_stream`.
+pub struct StatusStream {
+    receiver: async_mpsc::Receiver<Result<Status, Error>>,
+}
+
+impl StatusStream {
+    /// Wait for the next status, `None` once the I/O thread has stopped.
+    pub async fn next(&mut self) -> Option<Result<Status, Error>> {
+        self.receiver.recv().await
+    }
+}
+
+impl Stream for StatusStream {
+    type Item = Result<Status, Error>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+struct Subscriber {
+    interval: Duration,
+    due: Instant,
+    sender: async_mpsc::Sender<Result<Status, Error>>,
+}
+
+struct Worker<T: Transport> {
+    printer: Printer<T>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl<T: Transport> Worker<T> {
+    fn new(printer: Printer<T>) -> Self {
+        Worker {
+            printer,
+            subscribers: Vec::new(),
+        }
+    }
+
+    fn run(mut self, receiver: mpsc::Receiver<Request>) {
+        // AsyncPrinterが破棄されても、購読者が残っていればステータスを送り続ける
+        let mut connected = true;
+        loop {
+            // 次のステータス取得までリクエストを待つ
+            let due = self.subscribers.iter().map(|s| s.due).min();
+            let request = match due {
+                Some(due) if !connected => {
+                    thread::sleep(due.saturating_duration_since(Instant::now()));
+                    None
+                }
+                Some(due) => {
+                    let timeout = due.saturating_duration_since(Instant::now());
+                    match receiver.recv_timeout(timeout) {
+                        Ok(request) => Some(request),
+                        Err(mpsc::RecvTimeoutError::Timeout) => None,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => {
+                            connected = false;
+                            None
+                        }
+                    }
+                }
+                None if !connected => break,
+                None => match receiver.recv() {
+                    Ok(request) => Some(request),
+                    Err(_) => break,
+                },
+            };
+
+            match request {
+                Some(request) => self.handle(request),
+                None => self.poll_status(),
+            }
+        }
+        debug!("Printer I/O thread stopped");
+    }
+
+    fn handle(&mut self, request: Request) {
+        match request {
+            Request::Print(images, reply) => {
+                let _ = reply.send(self.printer.print(images.into_iter()));
+            }
+            Request::PrintTwoColor(images, reply) => {
+                let _ = reply.send(self.printer.print_two_color(images.into_iter()));
+            }
+            Request::CheckStatus(reply) => {
+                let _ = reply.send(self.printer.check_status());
+            }
+            Request::Subscribe(interval, sender) => self.subscribers.push(Subscriber {
+                interval,
+                due: Instant::now(),
+                sender,
+            }),
+        }
+    }
+
+    fn poll_status(&mut self) {
+        let now = Instant::now();
+        let printer = &self.printer;
+        self.subscribers.retain_mut(|subscriber| {
+            if subscriber.sender.is_closed() {
+                return false;
+            }
+            if subscriber.due <= now {
+                subscriber.due = now + subscriber.interval;
+                // 読み出されていなければ、問い合わせずに読み飛ばす
+                if subscriber.sender.capacity() > 0 {
+                    let _ = subscriber.sender.try_send(printer.check_status());
+                }
+            }
+            true
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fixture::default_printer;
+    use crate::{ContinuousType, Media};
+
+    #[tokio::test]
+    async fn test_async_print_and_status() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (device, printer) = default_printer();
+        let printer = AsyncPrinter::new(printer);
+
+        let pages = vec![vec![vec![0xAA; 90]; 3], vec![vec![0x55; 90]; 4]];
+        printer.print(pages.clone()).await.unwrap();
+        assert_eq!(device.pages(), pages);
+
+        let status = printer.check_status().await.unwrap();
+        assert_eq!(status.media(), Some(media));
+    }
+
+    #[tokio::test]
+    async fn test_status_stream() {
+        let (device, printer) = default_printer();
+        let printer = AsyncPrinter::new(printer);
+
+        let mut statuses = printer.status_stream(Duration::from_millis(10)).unwrap();
+        assert!(statuses.next().await.unwrap().unwrap().is_ok());
+
+        device.set_media(None);
+        // 取り残された古いステータスを読み飛ばす
+        loop {
+            let status = statuses.next().await.unwrap().unwrap();
+            if status.media().is_none() {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_stream_outlives_printer() {
+        let (device, printer) = default_printer();
+        let printer = AsyncPrinter::new(printer);
+
+        let mut statuses = printer.status_stream(Duration::from_millis(10)).unwrap();
+        drop(printer);
+        device.set_media(None);
+        loop {
+            let status = statuses.next().await.unwrap().unwrap();
+            if status.media().is_none() {
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Packed 1-bit page image.
+//!
+//! `Bitmap` keeps a whole page in one buffer together with its size and
+//! resolution, and addresses pixels in image coordinates: `x` from the left
+//! and `y` from the top of the image. Rows are stored the way the printer
+//! receives them, mirrored like `step_filter_normal` packs them, so
+//! converting to a `Matrix` is a plain copy.
+
+use crate::{
+    error::Error,
+    model::Model,
+    rows::{Row, RowSource},
+    Matrix,
+};
+
+/// Resolution of the print head, and of the feed direction in standard mode.
+const DEFAULT_DPI: u32 = 300;
+
+/// 1-bit image with explicit width, height and resolution.
+///
+/// A set pixel is printed, a cleared one is left blank.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{Bitmap, Matrix, Model};
+/// let mut label = Bitmap::for_model(Model::QL820NWB, 100);
+/// assert_eq!((label.width(), label.height()), (720, 100));
+///
+/// let mut frame = Bitmap::new(50, 20);
+/// frame.invert();
+/// label.blit(&frame, 10, 10);
+/// assert_eq!(label.get(10, 10), Some(true));
+/// assert_eq!(label.get(60, 10), Some(false));
+///
+/// let page: Matrix = label.into();
+/// assert_eq!(page[0].len(), 90);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    dpi: (u32, u32),
+    stride: usize,
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    /// Create a blank bitmap at 300 x 300 dpi.
+    pub fn new(width: u32, height: u32) -> Self {
+        let stride = (width as usize).div_ceil(8);
+        Bitmap {
+            width,
+            height,
+            dpi: (DEFAULT_DPI, DEFAULT_DPI),
+            stride,
+            data: vec![0x00; stride * height as usize],
+        }
+    }
+
+    /// Create a blank bitmap as wide as the print head of `model`.
+    pub fn for_model(model: Model, height: u32) -> Self {
+        Self::new(model.pins(), height)
+    }
+
+    /// Set the resolution across and along the tape.
+    ///
+    /// 300 x 600 dpi images are printed with `Config::high_resolution`.
+    pub fn with_dpi(self, horizontal: u32, vertical: u32) -> Self {
+        Bitmap {
+            dpi: (horizontal, vertical),
+            ..self
+        }
+    }
+
+    /// Build a bitmap from printer rows, 8 pixels per byte.
+    ///
+    /// # Returns
+    /// * `Ok(Bitmap)` - Bitmap as wide as the rows, at 300 x 300 dpi
+    /// * `Err(Error::InvalidBitmap)` - Rows have different lengths
+    pub fn from_matrix(matrix: &Matrix) -> Result<Self, Error> {
+        let stride = matrix.first().map_or(0, |row| row.len());
+        if let Some(y) = matrix.iter().position(|row| row.len() != stride) {
+            return Err(Error::InvalidBitmap(format!(
+                "row {} is {} bytes, expected {}",
+                y,
+                matrix[y].len(),
+                stride
+            )));
+        }
+
+        let mut bitmap = Self::new((stride * 8) as u32, matrix.len() as u32);
+        bitmap.data = matrix.concat();
+        Ok(bitmap)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Resolution across and along the tape, in dots per inch.
+    pub fn dpi(&self) -> (u32, u32) {
+        self.dpi
+    }
+
+    /// Pixel at `x`, `y`, `None` outside the bitmap.
+    pub fn get(&self, x: u32, y: u32) -> Option<bool> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let (index, mask) = self.locate(x, y);
+        Some(self.data[index] & mask != 0)
+    }
+
+    /// Set or clear the pixel at `x`, `y`.
+    ///
+    /// # Panics
+    /// If `x`, `y` is outside the bitmap.
+    pub fn set(&mut self, x: u32, y: u32, value: bool) {
+        assert!(
+            x < self.width && y < self.height,
+            "pixel ({}, {}) outside {}x{} bitmap",
+            x,
+            y,
+            self.width,
+            self.height
+        );
+        let (index, mask) = self.locate(x, y);
+        if value {
+            self.data[index] |= mask;
+        } else {
+            self.data[index] &= !mask;
+        }
+    }
+
+    /// Copy `source` with its top-left corner at `x`, `y`.
+    ///
+    /// Pixels falling outside this bitmap are dropped, so the position may be negative.
+    pub fn blit(&mut self, source: &Bitmap, x: i64, y: i64) {
+        // 行は左右反転して格納されているので、格納順でのビットのずれは行によらず一定
+        let shift = self.width as i64 - source.width as i64 - x;
+        // 転送先に収まる、格納順での転送元のビットの範囲
+        let lo = (-shift).max(0);
+        let hi = (self.width as i64 - shift).min(source.width as i64);
+        if lo >= hi {
+            return;
+        }
+
+        for sy in 0..source.height as usize {
+            let ty = y + sy as i64;
+            if ty < 0 || ty >= self.height as i64 {
+                continue;
+            }
+            let src = &source.data[sy * source.stride..(sy + 1) * source.stride];
+            let start = ty as usize * self.stride;
+            let dst = &mut self.data[start..start + self.stride];
+
+            // バイト境界が揃っていれば、端以外はそのままコピーする
+            let (first, last) = ((lo + 7) / 8, hi / 8);
+            if shift % 8 == 0 && first < last {
+                let offset = shift / 8;
+                dst[(first + offset) as usize..(last + offset) as usize]
+                    .copy_from_slice(&src[first as usize..last as usize]);
+                copy_bits(src, lo, first * 8, dst, shift);
+                copy_bits(src, last * 8, hi, dst, shift);
+            } else {
+                copy_bits(src, lo, hi, dst, shift);
+            }
+        }
+    }
+
+    /// Copy of the area of `width` x `height` pixels at `x`, `y`, clipped to the bitmap.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Bitmap {
+        let width = width.min(self.width.saturating_sub(x));
+        let height = height.min(self.height.saturating_sub(y));
+        let mut cropped = Bitmap::new(width, height).with_dpi(self.dpi.0, self.dpi.1);
+        cropped.blit(self, -(x as i64), -(y as i64));
+        cropped
+    }
+
+    /// Swap printed and blank pixels.
+    pub fn invert(&mut self) {
+        for byte in self.data.iter_mut() {
+            *byte = !*byte;
+        }
+        // 行末の余りビットは空白のままにする
+        let rest = self.width % 8;
+        if rest != 0 {
+            let mask = 0xFFu8 << (8 - rest);
+            for row in self.data.chunks_mut(self.stride) {
+                row[self.stride - 1] &= mask;
+            }
+        }
+    }
+
+    /// Rows as sent to the printer, from the top of the image.
+    pub fn rows(&self) -> impl Iterator<Item = &[u8]> + '_ {
+        (0..self.height as usize).map(move |y| &self.data[y * self.stride..(y + 1) * self.stride])
+    }
+
+    /// Copy the rows into a `Matrix`.
+    pub fn to_matrix(&self) -> Matrix {
+        self.rows().map(|row| row.to_vec()).collect()
+    }
+
+    /// Consume the bitmap into a `RowSource`, for `Printer::print_rows`.
+    pub fn into_rows(self) -> BitmapRows {
+        BitmapRows {
+            bitmap: self,
+            next: 0,
+        }
+    }
+
+    /// Byte index and bit mask of a pixel, the first pixel is the last bit of the row.
+    fn locate(&self, x: u32, y: u32) -> (usize, u8) {
+        let mirrored = (self.width - 1 - x) as usize;
+        let index = y as usize * self.stride + mirrored / 8;
+        (index, 0x80 >> (mirrored % 8))
+    }
+}
+
+impl From<Bitmap> for Matrix {
+    fn from(bitmap: Bitmap) -> Self {
+        bitmap.to_matrix()
+    }
+}
+
+/// Rows of a `Bitmap` handed out one at a time, see `Bitmap::into_rows`.
+#[derive(Debug, Clone)]
+pub struct BitmapRows {
+    bitmap: Bitmap,
+    next: usize,
+}
+
+impl RowSource for BitmapRows {
+    fn raster_count(&self) -> u32 {
+        self.bitmap.height
+    }
+
+    fn next_row(&mut self) -> Option<Row> {
+        if self.next >= self.bitmap.height as usize {
+            return None;
+        }
+        let stride = self.bitmap.stride;
+        let row = self.bitmap.data[self.next * stride..(self.next + 1) * stride].to_vec();
+        self.next += 1;
+        Some(row)
+    }
+}
+
+/// Copy the bits `lo..hi` of `src` to `shift` bits further in `dst`, the first bit is the MSB.
+fn copy_bits(src: &[u8], lo: i64, hi: i64, dst: &mut [u8], shift: i64) {
+    if lo >= hi {
+        return;
+    }
+    for j in lo / 8..(hi + 7) / 8 {
+        // このバイトのうち範囲内のビット
+        let first = (lo - j * 8).max(0) as u32;
+        let end = (hi - j * 8).min(8) as u32;
+        let mask = (0xFFu8 >> first) & (0xFFu8 << (8 - end));
+
+        // 2バイトにまたがるので16ビットでずらす
+        let position = j * 8 + shift;
+        let (index, offset) = (position.div_euclid(8), position.rem_euclid(8) as u32);
+        let value = ((src[j as usize] & mask) as u16) << 8 >> offset;
+        let keep = (mask as u16) << 8 >> offset;
+        for (index, part) in [(index, 8), (index + 1, 0)] {
+            let mask = (keep >> part) as u8;
+            if mask != 0 {
+                let byte = &mut dst[index as usize];
+                *byte = (*byte & !mask) | (value >> part) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fixture::default_printer;
+    use crate::{step_filter_normal, Error, NORMAL_PRINTER_WIDTH};
+
+    #[test]
+    fn test_same_layout_as_step_filter() {
+        let (width, height) = (NORMAL_PRINTER_WIDTH, 3);
+        // 左上とその右隣、最終行の右端だけ黒
+        let mut gray = vec![0xFFu8; (width * height) as usize];
+        gray[0] = 0;
+        gray[1] = 0;
+        gray[(width * height - 1) as usize] = 0;
+        let matrix = step_filter_normal(80, height, gray);
+
+        let bitmap = Bitmap::from_matrix(&matrix).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height()), (width, height));
+        assert_eq!(bitmap.get(0, 0), Some(true));
+        assert_eq!(bitmap.get(1, 0), Some(true));
+        assert_eq!(bitmap.get(2, 0), Some(false));
+        assert_eq!(bitmap.get(width - 1, 2), Some(true));
+        assert_eq!(bitmap.get(width, 2), None);
+
+        let mut drawn = Bitmap::new(width, height);
+        drawn.set(0, 0, true);
+        drawn.set(1, 0, true);
+        drawn.set(width - 1, 2, true);
+        assert_eq!(drawn.to_matrix(), matrix);
+    }
+
+    #[test]
+    fn test_from_matrix_rejects_ragged_rows() {
+        let matrix = vec![vec![0x00; 90], vec![0x00; 89]];
+        assert!(matches!(Bitmap::from_matrix(&matrix), Err(Error::InvalidBitmap(_))));
+    }
+
+    #[test]
+    fn test_invert_keeps_padding_blank() {
+        let mut bitmap = Bitmap::new(10, 2);
+        bitmap.invert();
+        assert!((0..10).all(|x| bitmap.get(x, 1) == Some(true)));
+        let rows: Vec<&[u8]> = bitmap.rows().collect();
+        assert_eq!(rows[0], &[0xFF, 0xC0]);
+    }
+
+    #[test]
+    fn test_blit_and_crop_clip() {
+        let mut square = Bitmap::new(4, 4);
+        square.invert();
+
+        let mut page = Bitmap::new(8, 8).with_dpi(300, 600);
+        page.blit(&square, -2, 6);
+        let set: Vec<(u32, u32)> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .filter(|&(x, y)| page.get(x, y) == Some(true))
+            .collect();
+        assert_eq!(set, vec![(0, 6), (1, 6), (0, 7), (1, 7)]);
+
+        let corner = page.crop(1, 5, 10, 10);
+        assert_eq!((corner.width(), corner.height(), corner.dpi()), (7, 3, (300, 600)));
+        assert_eq!(corner.get(0, 1), Some(true));
+        assert_eq!(corner.get(1, 1), Some(false));
+    }
+
+    #[test]
+    fn test_blit_matches_pixel_copy() {
+        // 1画素ずつコピーした結果と、あらゆるずれ・幅で一致する
+        for width in [5, 8, 13, 24] {
+            let mut source = Bitmap::new(width, 3);
+            for (x, y) in (0..width).flat_map(|x| (0..3).map(move |y| (x, y))) {
+                source.set(x, y, (x * 7 + y * 3) % 5 < 2);
+            }
+            for x in -30..40 {
+                let mut page = Bitmap::new(29, 4);
+                page.invert();
+                let mut expected = page.clone();
+                for (sx, sy) in (0..width).flat_map(|sx| (0..3).map(move |sy| (sx, sy))) {
+                    let (tx, ty) = (x + sx as i64, 1 + sy as i64);
+                    if (0..29).contains(&tx) {
+                        expected.set(tx as u32, ty as u32, source.get(sx, sy).unwrap());
+                    }
+                }
+                page.blit(&source, x, 1);
+                assert_eq!(page, expected, "width {} at {}", width, x);
+            }
+        }
+    }
+
+    #[test]
+    fn test_into_rows() {
+        let mut bitmap = Bitmap::new(16, 3);
+        bitmap.set(0, 1, true);
+        let matrix = bitmap.to_matrix();
+
+        let mut rows = bitmap.into_rows();
+        assert_eq!(rows.raster_count(), 3);
+        let collected: Matrix = std::iter::from_fn(|| rows.next_row()).collect();
+        assert_eq!(collected, matrix);
+    }
+
+    #[test]
+    fn test_print_bitmaps() {
+        let (device, printer) = default_printer();
+
+        let mut label = Bitmap::for_model(Model::QL820NWB, 8);
+        label.set(0, 0, true);
+        printer.print_bitmaps(vec![label.clone()].into_iter()).unwrap();
+        assert_eq!(device.pages(), vec![label.to_matrix()]);
+
+        let narrow = Bitmap::new(640, 8);
+        let high = label.clone().with_dpi(300, 600);
+        for image in [narrow, high].iter() {
+            let result = printer.print_bitmaps(vec![image.clone()].into_iter());
+            match result {
+                Err(Error::JobInterrupted { printed, source }) => {
+                    assert!(printed.is_empty());
+                    assert!(matches!(*source, Error::InvalidBitmap(_)));
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+        assert_eq!(device.take_pages().len(), 1);
+
+        // 不正なページは取り出したときに検査され、それ以降は取り出さない
+        let mut pulled = 0;
+        let images = vec![label.clone(), Bitmap::new(640, 8), label.clone()]
+            .into_iter()
+            .inspect(|_| pulled += 1);
+        match printer.print_bitmaps(images) {
+            Err(Error::JobInterrupted { printed, .. }) => assert_eq!(printed, vec![0]),
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(pulled, 2);
+        assert_eq!(device.pages(), vec![label.to_matrix()]);
+    }
+}
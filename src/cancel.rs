@@ -0,0 +1,98 @@
+//! Cancellation of a running print job.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Handle to cancel the print job of a `Printer` from another thread.
+///
+/// Obtained with `Printer::cancel_token`. Clones share the same state, so
+/// the token can be handed to a UI thread while `print` blocks.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{ContinuousType, Error, Media, Model, SimulatedPrinter};
+/// # let media = Media::Continuous(ContinuousType::Continuous62);
+/// # let (_, printer) = SimulatedPrinter::connect(Model::QL820NWB, media);
+/// let token = printer.cancel_token();
+///
+/// // The third page is taken before the second one is sent
+/// let pages = (0..10).map(|i| {
+///     if i == 2 {
+///         token.cancel();
+///     }
+///     vec![vec![0xFF; 90]; 10]
+/// });
+/// match printer.print(pages) {
+///     Err(Error::Cancelled { completed }) => assert_eq!(completed, 1),
+///     other => panic!("{:?}", other),
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the running job to stop.
+    ///
+    /// The printer stops between pages, between the chunks of a long page or
+    /// while waiting for a status, flushes its buffer and `print` returns
+    /// `Error::Cancelled`. A job which has not started yet is cancelled
+    /// before its first page. The request is cleared when the job returns.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Guard clearing the request when the job holding it returns.
+    pub(crate) fn job(&self) -> JobGuard<'_> {
+        JobGuard { token: self }
+    }
+}
+
+pub(crate) struct JobGuard<'a> {
+    token: &'a CancelToken,
+}
+
+impl Drop for JobGuard<'_> {
+    fn drop(&mut self) {
+        self.token.cancelled.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::simulator::fixture::{default_printer, pattern};
+    use crate::{Error, JobEvent};
+
+    #[test]
+    fn test_cancel_between_pages() {
+        let (device, mut printer) = default_printer();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        printer.set_observer(sender);
+
+        let token = printer.cancel_token();
+        let images = (0..5).map(move |i| {
+            // 3ページ目の送信前に取り出される
+            if i == 3 {
+                token.cancel();
+            }
+            pattern(2)
+        });
+        let result = printer.print(images);
+        assert!(matches!(result, Err(Error::Cancelled { completed: 2 })));
+        assert_eq!(device.take_pages().len(), 2);
+        assert_eq!(receiver.try_iter().last(), Some(JobEvent::Cancelled { completed: 2 }));
+
+        // 次のジョブには影響しない
+        printer.print(vec![pattern(2)].into_iter()).unwrap();
+        assert_eq!(device.pages().len(), 1);
+    }
+}
@@ -0,0 +1,561 @@
+//! Raster command decoder and protocol linter.
+//!
+//! `RasterDecoder` is the inverse of `RasterEncoder`: it reads a Brother
+//! raster byte stream, ours or one captured from Brother's own driver, into
+//! typed `RasterCommand`s. `lint` then checks the command list for mistakes
+//! which make the printer refuse a job, usually by blinking its red LED.
+
+use std::fmt;
+
+use crate::{error::Error, model::Model};
+
+/// One command of the raster protocol.
+///
+/// Raster lines are stored expanded, i.e. PackBits compression is already
+/// undone when `Compression(true)` was in effect.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RasterCommand {
+    /// Run of `0x00` padding bytes
+    Invalidate(usize),
+    /// ESC @
+    Initialize,
+    /// ESC i S
+    StatusRequest,
+    /// ESC i a, `0x01` is raster mode
+    SwitchMode(u8),
+    /// ESC i !, `true` when automatic status notification is on
+    AutoStatus(bool),
+    /// ESC i z
+    PrintInformation(PrintInformation),
+    /// ESC i d, feed amount in dots
+    Margin(u16),
+    /// ESC i M
+    VariousMode { auto_cut: bool },
+    /// ESC i A, number of labels between cuts
+    CutEach(u8),
+    /// ESC i K
+    ExpandedMode {
+        two_colors: bool,
+        cut_at_end: bool,
+        high_resolution: bool,
+    },
+    /// M, `true` for PackBits compression
+    Compression(bool),
+    /// g
+    Raster(Vec<u8>),
+    /// w, `color` is `0x01` for black and `0x02` for red
+    ColorRaster { color: u8, data: Vec<u8> },
+    /// Z
+    ZeroRaster,
+    /// FF
+    Print,
+    /// Control-Z
+    PrintAndEject,
+    /// Byte which does not start a known command
+    Unknown(u8),
+}
+
+/// Fields of the ESC i z print information command.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PrintInformation {
+    /// n1: which of the following fields are valid
+    pub valid_flags: u8,
+    /// n2: `0x0A` continuous, `0x0B` die-cut
+    pub media_type: u8,
+    /// n3
+    pub width_mm: u8,
+    /// n4
+    pub length_mm: u8,
+    /// n5-n8
+    pub raster_count: u32,
+    /// n9 is `0` on the first page of a job
+    pub starting_page: bool,
+}
+
+/// Streaming decoder for the raster protocol.
+///
+/// Bytes can be pushed in arbitrary chunks, commands are returned as soon
+/// as they are complete.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{RasterCommand, RasterDecoder};
+/// let mut decoder = RasterDecoder::new();
+/// decoder.push(&[0x1B, 0x69]);
+/// assert_eq!(decoder.next_command()?, None);
+/// decoder.push(&[0x53]);
+/// assert_eq!(decoder.next_command()?, Some(RasterCommand::StatusRequest));
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct RasterDecoder {
+    input: Vec<u8>,
+    offset: usize,
+    compression: bool,
+}
+
+impl RasterDecoder {
+    pub fn new() -> Self {
+        RasterDecoder::default()
+    }
+
+    /// Append bytes received from the host.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.input.extend_from_slice(bytes);
+    }
+
+    /// Number of buffered bytes not decoded yet.
+    ///
+    /// Once `next_command` returned `None`, these are the start of an
+    /// incomplete command.
+    pub fn pending(&self) -> usize {
+        self.input.len()
+    }
+
+    /// Offset in the whole stream of the next command.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Decode the next complete command.
+    ///
+    /// # Returns
+    /// * `Ok(Some(RasterCommand))` - Next command
+    /// * `Ok(None)` - More bytes are needed
+    /// * `Err(Error::UnknownCommand)` - An escape sequence of unknown length,
+    ///   decoding cannot continue past it
+    pub fn next_command(&mut self) -> Result<Option<RasterCommand>, Error> {
+        let (command, len) = match self.parse() {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        let command = command?;
+        self.input.drain(..len);
+        self.offset += len;
+
+        match command {
+            RasterCommand::Initialize => self.compression = false,
+            RasterCommand::Compression(flag) => self.compression = flag,
+            _ => {}
+        }
+        Ok(Some(command))
+    }
+
+    fn parse(&self) -> Option<(Result<RasterCommand, Error>, usize)> {
+        let buf = &self.input;
+        let need = |n: usize| if buf.len() >= n { Some(()) } else { None };
+
+        let (command, len) = match *buf.first()? {
+            0x00 => {
+                let n = buf.iter().take_while(|&&b| b == 0x00).count();
+                (RasterCommand::Invalidate(n), n)
+            }
+            0x1B => match *buf.get(1)? {
+                0x40 => (RasterCommand::Initialize, 2),
+                0x69 => match *buf.get(2)? {
+                    0x53 => (RasterCommand::StatusRequest, 3),
+                    0x61 => (RasterCommand::SwitchMode(*buf.get(3)?), 4),
+                    0x21 => (RasterCommand::AutoStatus(*buf.get(3)? == 0x00), 4),
+                    0x4D => (
+                        RasterCommand::VariousMode {
+                            auto_cut: *buf.get(3)? & 0b0100_0000 != 0,
+                        },
+                        4,
+                    ),
+                    0x41 => (RasterCommand::CutEach(*buf.get(3)?), 4),
+                    0x4B => {
+                        let mode = *buf.get(3)?;
+                        let command = RasterCommand::ExpandedMode {
+                            two_colors: mode & 0b0000_0001 != 0,
+                            cut_at_end: mode & 0b0000_1000 != 0,
+                            high_resolution: mode & 0b0100_0000 != 0,
+                        };
+                        (command, 4)
+                    }
+                    0x64 => {
+                        need(5)?;
+                        (RasterCommand::Margin(u16::from_le_bytes([buf[3], buf[4]])), 5)
+                    }
+                    0x7A => {
+                        need(13)?;
+                        let info = PrintInformation {
+                            valid_flags: buf[3],
+                            media_type: buf[4],
+                            width_mm: buf[5],
+                            length_mm: buf[6],
+                            raster_count: u32::from_le_bytes([buf[7], buf[8], buf[9], buf[10]]),
+                            starting_page: buf[11] == 0x00,
+                        };
+                        (RasterCommand::PrintInformation(info), 13)
+                    }
+                    // パラメータ長が分からないので、以降は読めない
+                    _ => return Some((Err(Error::UnknownCommand(self.offset)), 0)),
+                },
+                _ => return Some((Err(Error::UnknownCommand(self.offset)), 0)),
+            },
+            0x4D => (RasterCommand::Compression(*buf.get(1)? == 0x02), 2),
+            0x67 => {
+                let n = *buf.get(2)? as usize;
+                need(3 + n)?;
+                (RasterCommand::Raster(self.expand(&buf[3..3 + n])), 3 + n)
+            }
+            0x77 => {
+                let n = *buf.get(2)? as usize;
+                need(3 + n)?;
+                let command = RasterCommand::ColorRaster {
+                    color: buf[1],
+                    data: self.expand(&buf[3..3 + n]),
+                };
+                (command, 3 + n)
+            }
+            0x5A => (RasterCommand::ZeroRaster, 1),
+            0x0C => (RasterCommand::Print, 1),
+            0x1A => (RasterCommand::PrintAndEject, 1),
+            other => (RasterCommand::Unknown(other), 1),
+        };
+        Some((Ok(command), len))
+    }
+
+    fn expand(&self, data: &[u8]) -> Vec<u8> {
+        if self.compression {
+            unpack_bits(data)
+        } else {
+            data.to_vec()
+        }
+    }
+}
+
+/// Decode a complete raster stream.
+///
+/// # Returns
+/// * `Ok(Vec<RasterCommand>)` - Commands in stream order
+/// * `Err(Error::TruncatedStream)` - The stream ends inside a command
+/// * `Err(Error::UnknownCommand)` - The stream has an escape sequence of unknown length
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{decode, Config, ContinuousType, Media, Model, RasterCommand, RasterEncoder};
+/// let config = Config::new(Model::QL800, "serial".to_string(),
+///                          Media::Continuous(ContinuousType::Continuous29));
+/// let job = RasterEncoder::new(config)?.encode(vec![vec![vec![0x00; 90]; 3]])?;
+///
+/// let commands = decode(&job)?;
+/// assert_eq!(commands.last(), Some(&RasterCommand::PrintAndEject));
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+pub fn decode(bytes: &[u8]) -> Result<Vec<RasterCommand>, Error> {
+    let mut decoder = RasterDecoder::new();
+    decoder.push(bytes);
+
+    let mut commands = Vec::new();
+    while let Some(command) = decoder.next_command()? {
+        commands.push(command);
+    }
+    if decoder.pending() > 0 {
+        return Err(Error::TruncatedStream(decoder.offset()));
+    }
+    Ok(commands)
+}
+
+/// Problem found by `lint`.
+///
+/// Pages are counted from 0, lines from 0 within their page.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    /// ESC i z announced a different number of raster lines than were sent.
+    RasterCountMismatch {
+        page: usize,
+        announced: u32,
+        received: u32,
+    },
+    /// A raster line does not cover the print head of the model.
+    LineWidth {
+        page: usize,
+        line: usize,
+        expected: usize,
+        actual: usize,
+    },
+    /// Compression was enabled on a model which does not support it.
+    CompressionUnsupported(Model),
+    /// Raster lines were sent without a preceding ESC i z.
+    MissingPrintInformation { page: usize },
+    /// n9 of ESC i z does not match the position of the page in the job.
+    StartingPageFlag { page: usize, starting_page: bool },
+    /// The job does not end with Control-Z.
+    MissingEject,
+    /// Byte which does not start a known command.
+    UnknownCommand(u8),
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::RasterCountMismatch {
+                page,
+                announced,
+                received,
+            } => write!(
+                f,
+                "page {}: ESC i z announces {} raster lines but {} were sent",
+                page, announced, received
+            ),
+            Lint::LineWidth {
+                page,
+                line,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "page {}, line {}: {} bytes wide, the print head needs {}",
+                page, line, actual, expected
+            ),
+            Lint::CompressionUnsupported(model) => {
+                write!(f, "{:?} does not support compression", model)
+            }
+            Lint::MissingPrintInformation { page } => {
+                write!(f, "page {}: raster data without ESC i z", page)
+            }
+            Lint::StartingPageFlag {
+                page,
+                starting_page,
+            } => write!(
+                f,
+                "page {}: ESC i z marks the page as {}",
+                page,
+                if *starting_page {
+                    "starting page"
+                } else {
+                    "continuation page"
+                }
+            ),
+            Lint::MissingEject => write!(f, "job does not end with Control-Z"),
+            Lint::UnknownCommand(code) => write!(f, "unknown command {:#04x}", code),
+        }
+    }
+}
+
+/// Check a decoded job against the rules of the raster protocol for `model`.
+///
+/// # Returns
+/// Every problem found, an empty vector for a clean job
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{decode, lint, Lint, Model};
+/// // ESC i z announcing 2 lines, followed by a single 90 byte line
+/// let mut job = vec![0x1B, 0x69, 0x7A, 0x4E, 0x0A, 29, 0, 2, 0, 0, 0, 0, 0];
+/// job.extend_from_slice(&[0x67, 0x00, 90]);
+/// job.extend_from_slice(&[0x00; 90]);
+/// job.push(0x1A);
+///
+/// let lints = lint(&decode(&job)?, Model::QL800);
+/// assert_eq!(lints, vec![Lint::RasterCountMismatch { page: 0, announced: 2, received: 1 }]);
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+pub fn lint(commands: &[RasterCommand], model: Model) -> Vec<Lint> {
+    let width = model.capabilities().line_bytes();
+    let mut lints = Vec::new();
+
+    let mut page = 0;
+    let mut line = 0;
+    let mut info: Option<PrintInformation> = None;
+    let mut lines: u32 = 0;
+    let mut color_lines: u32 = 0;
+    let mut data_sent = false;
+    let mut ejected = false;
+
+    for command in commands {
+        match command {
+            RasterCommand::Compression(true) if !model.capabilities().compression => {
+                lints.push(Lint::CompressionUnsupported(model));
+            }
+            RasterCommand::PrintInformation(i) => {
+                if i.starting_page != (page == 0) {
+                    lints.push(Lint::StartingPageFlag {
+                        page,
+                        starting_page: i.starting_page,
+                    });
+                }
+                info = Some(*i);
+            }
+            RasterCommand::Raster(data) | RasterCommand::ColorRaster { data, .. } => {
+                if data.len() != width {
+                    lints.push(Lint::LineWidth {
+                        page,
+                        line,
+                        expected: width,
+                        actual: data.len(),
+                    });
+                }
+                if matches!(command, RasterCommand::ColorRaster { .. }) {
+                    color_lines += 1;
+                } else {
+                    lines += 1;
+                }
+                line += 1;
+                data_sent = true;
+                ejected = false;
+            }
+            RasterCommand::ZeroRaster => {
+                lines += 1;
+                line += 1;
+                data_sent = true;
+                ejected = false;
+            }
+            RasterCommand::Print | RasterCommand::PrintAndEject => {
+                // 2色印刷では黒と赤の2ラインで1ラスターとなる
+                let received = lines + color_lines / 2;
+                match info.take() {
+                    Some(i) if i.raster_count != received => {
+                        lints.push(Lint::RasterCountMismatch {
+                            page,
+                            announced: i.raster_count,
+                            received,
+                        });
+                    }
+                    Some(_) => {}
+                    None if data_sent => lints.push(Lint::MissingPrintInformation { page }),
+                    None => {}
+                }
+                ejected = matches!(command, RasterCommand::PrintAndEject);
+                page += 1;
+                line = 0;
+                lines = 0;
+                color_lines = 0;
+                data_sent = false;
+            }
+            RasterCommand::Unknown(code) => lints.push(Lint::UnknownCommand(*code)),
+            _ => {}
+        }
+    }
+
+    if (page > 0 || data_sent) && !ejected {
+        lints.push(Lint::MissingEject);
+    }
+    lints
+}
+
+/// Expand a TIFF PackBits encoded raster line.
+pub(crate) fn unpack_bits(data: &[u8]) -> Vec<u8> {
+    let mut row = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let n = data[i] as i8;
+        i += 1;
+        if n >= 0 {
+            let end = (i + n as usize + 1).min(data.len());
+            row.extend_from_slice(&data[i..end]);
+            i = end;
+        } else if n != -128 {
+            if let Some(&value) = data.get(i) {
+                row.resize(row.len() + (1 - n as isize) as usize, value);
+            }
+            i += 1;
+        }
+    }
+    row
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, ContinuousType, Media, RasterEncoder};
+
+    #[test]
+    fn test_decode_encoded_job() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let config = Config::new(Model::QL820NWB, "serial".to_string(), media).compress(true);
+        let page: Vec<Vec<u8>> = (0..3).map(|y| vec![y as u8; 90]).collect();
+        let job = RasterEncoder::new(config)
+            .unwrap()
+            .encode(vec![page.clone(), page.clone()])
+            .unwrap();
+
+        let commands = decode(&job).unwrap();
+        assert_eq!(commands[0], RasterCommand::Invalidate(400));
+        assert_eq!(commands[1], RasterCommand::Initialize);
+        assert!(commands.contains(&RasterCommand::Compression(true)));
+
+        let rows: Vec<Vec<u8>> = commands
+            .iter()
+            .filter_map(|c| match c {
+                RasterCommand::Raster(data) => Some(data.clone()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rows, [page.clone(), page].concat());
+
+        assert!(lint(&commands, Model::QL820NWB).is_empty());
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let result = decode(&[0x1B, 0x40, 0x67, 0x00, 90, 0x00]);
+        assert!(matches!(result, Err(Error::TruncatedStream(2))));
+    }
+
+    #[test]
+    fn test_decode_unknown_escape() {
+        // ESC i U のようにパラメータ長の分からないコマンドで止まる
+        let result = decode(&[0x1B, 0x40, 0x1B, 0x69, 0x55, 0x77, 0x01, 0x0C]);
+        assert!(matches!(result, Err(Error::UnknownCommand(2))));
+        assert!(matches!(decode(&[0x1B, 0x99, 0x0C]), Err(Error::UnknownCommand(0))));
+
+        // 単独の不明なバイトは1バイトとして読み飛ばす
+        assert_eq!(
+            decode(&[0x99, 0x0C]).unwrap(),
+            vec![RasterCommand::Unknown(0x99), RasterCommand::Print]
+        );
+    }
+
+    #[test]
+    fn test_lint_rules() {
+        let info = |raster_count, starting_page| {
+            RasterCommand::PrintInformation(PrintInformation {
+                valid_flags: 0x4E,
+                media_type: 0x0A,
+                width_mm: 29,
+                length_mm: 0,
+                raster_count,
+                starting_page,
+            })
+        };
+        let commands = vec![
+            RasterCommand::Compression(true),
+            info(1, true),
+            RasterCommand::Raster(vec![0x00; 90]),
+            RasterCommand::Print,
+            info(1, true),
+            RasterCommand::Raster(vec![0x00; 89]),
+            RasterCommand::Print,
+            RasterCommand::Unknown(0x99),
+        ];
+
+        assert_eq!(
+            lint(&commands, Model::QL800),
+            vec![
+                Lint::CompressionUnsupported(Model::QL800),
+                Lint::StartingPageFlag {
+                    page: 1,
+                    starting_page: true
+                },
+                Lint::LineWidth {
+                    page: 1,
+                    line: 0,
+                    expected: 90,
+                    actual: 89
+                },
+                Lint::UnknownCommand(0x99),
+                Lint::MissingEject,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unpack_bits() {
+        assert_eq!(
+            unpack_bits(&[0xFD, 0xAA, 0x01, 0x01, 0x02]),
+            vec![0xAA, 0xAA, 0xAA, 0xAA, 0x01, 0x02]
+        );
+    }
+}
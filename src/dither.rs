@@ -0,0 +1,278 @@
+//! Grayscale to 1-bit conversion for photos and gradients.
+//!
+//! A hard threshold, like `step_filter_normal` applies, prints every area
+//! darker than the threshold solid black. The error diffusion and ordered
+//! dithering methods here keep gradients visible as a dot pattern.
+
+use crate::{bitmap::Bitmap, error::Error};
+
+/// Method used by `dither` to turn gray levels into printed dots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dither {
+    /// Pixels brighter than the value are left blank, the rest are printed
+    Threshold(u8),
+    /// Error diffusion to 4 neighbours, smooth gradients
+    FloydSteinberg,
+    /// Diffuses 3/4 of the error, higher contrast for line art and logos
+    Atkinson,
+    /// Error diffusion to 12 neighbours, the least visible artifacts
+    Stucki,
+    /// Ordered dithering with a 4x4 Bayer matrix, regular cross-hatch pattern
+    Bayer4,
+    /// Ordered dithering with an 8x8 Bayer matrix, more gray levels
+    Bayer8,
+}
+
+// (dx, dy, weight), 重みの合計で割る
+const FLOYD_STEINBERG: (&[(i64, usize, i32)], i32) = (&[(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)], 16);
+const ATKINSON: (&[(i64, usize, i32)], i32) = (
+    &[(1, 0, 1), (2, 0, 1), (-1, 1, 1), (0, 1, 1), (1, 1, 1), (0, 2, 1)],
+    8,
+);
+const STUCKI: (&[(i64, usize, i32)], i32) = (
+    &[
+        (1, 0, 8),
+        (2, 0, 4),
+        (-2, 1, 2),
+        (-1, 1, 4),
+        (0, 1, 8),
+        (1, 1, 4),
+        (2, 1, 2),
+        (-2, 2, 1),
+        (-1, 2, 2),
+        (0, 2, 4),
+        (1, 2, 2),
+        (2, 2, 1),
+    ],
+    42,
+);
+
+/// Convert a grayscale image to a `Bitmap` for printing.
+///
+/// The image is read row by row from the top left, one byte per pixel,
+/// 0 for black and 255 for white. Use `NORMAL_PRINTER_WIDTH` or
+/// `WIDE_PRINTER_WIDTH` as `width` to fill the print head.
+///
+/// With `high_resolution` the printer feeds at 600 dpi while the head stays
+/// at 300 dpi, so every row is printed twice to keep square pixels from
+/// coming out half as tall. The bitmap is then 300 x 600 dpi and twice as
+/// high, ready for `Printer::print_bitmaps` with `Config::high_resolution`.
+///
+/// # Arguments
+/// * `method` - Dithering method
+/// * `width` - Image width in pixels
+/// * `height` - Image height in pixels
+/// * `gray` - Grayscale image data (width × height bytes)
+/// * `high_resolution` - Produce a 300 x 600 dpi bitmap
+///
+/// # Returns
+/// * `Ok(Bitmap)` - Dithered image
+/// * `Err(Error::InvalidBitmap)` - Data size doesn't match the dimensions, or
+///   the bitmap would be too large
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{dither, Dither, NORMAL_PRINTER_WIDTH};
+/// let width = NORMAL_PRINTER_WIDTH;
+/// let height = 100;
+/// // Horizontal gradient from black to white
+/// let gray: Vec<u8> = (0..width * height).map(|i| (i % width * 255 / width) as u8).collect();
+///
+/// let bitmap = dither(Dither::FloydSteinberg, width, height, &gray, true)?;
+/// assert_eq!((bitmap.height(), bitmap.dpi()), (200, (300, 600)));
+/// assert_eq!(bitmap.get(0, 0), Some(true));
+/// assert_eq!(bitmap.get(width - 1, 0), Some(false));
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+pub fn dither(
+    method: Dither,
+    width: u32,
+    height: u32,
+    gray: &[u8],
+    high_resolution: bool,
+) -> Result<Bitmap, Error> {
+    if (width as usize).checked_mul(height as usize) != Some(gray.len()) {
+        return Err(Error::InvalidBitmap(format!(
+            "{} bytes of gray levels for a {}x{} image",
+            gray.len(),
+            width,
+            height
+        )));
+    }
+
+    // 高解像度では縦方向に2倍に引き伸ばす
+    let scale = if high_resolution { 2 } else { 1 };
+    let rows = height.checked_mul(scale).ok_or_else(|| {
+        Error::InvalidBitmap(format!("{} rows are too many for high resolution", height))
+    })?;
+    let mut bitmap = Bitmap::new(width, rows);
+    if high_resolution {
+        bitmap = bitmap.with_dpi(300, 600);
+    }
+    let width = width as usize;
+    let level = |x: usize, y: usize| gray[(y / scale as usize) * width + x];
+
+    match method {
+        Dither::Threshold(threshold) => ordered(&mut bitmap, level, |_, _| threshold as i32),
+        Dither::FloydSteinberg => diffuse(&mut bitmap, level, FLOYD_STEINBERG),
+        Dither::Atkinson => diffuse(&mut bitmap, level, ATKINSON),
+        Dither::Stucki => diffuse(&mut bitmap, level, STUCKI),
+        Dither::Bayer4 => ordered(&mut bitmap, level, bayer_threshold(4)),
+        Dither::Bayer8 => ordered(&mut bitmap, level, bayer_threshold(8)),
+    }
+    Ok(bitmap)
+}
+
+/// Print every pixel not brighter than its threshold.
+fn ordered(
+    bitmap: &mut Bitmap,
+    level: impl Fn(usize, usize) -> u8,
+    threshold: impl Fn(usize, usize) -> i32,
+) {
+    for y in 0..bitmap.height() as usize {
+        for x in 0..bitmap.width() as usize {
+            if level(x, y) as i32 <= threshold(x, y) {
+                bitmap.set(x as u32, y as u32, true);
+            }
+        }
+    }
+}
+
+/// Threshold of an `n` x `n` Bayer matrix, spread evenly over 0 to 255.
+fn bayer_threshold(n: usize) -> impl Fn(usize, usize) -> i32 {
+    // 2x2から再帰的に拡大する: M(2n) = [[4M, 4M+2], [4M+3, 4M+1]]
+    let mut matrix = vec![vec![0usize]];
+    while matrix.len() < n {
+        let size = matrix.len();
+        let mut next = vec![vec![0; size * 2]; size * 2];
+        for (y, row) in matrix.iter().enumerate() {
+            for (x, value) in row.iter().enumerate() {
+                next[y][x] = 4 * value;
+                next[y][x + size] = 4 * value + 2;
+                next[y + size][x] = 4 * value + 3;
+                next[y + size][x + size] = 4 * value + 1;
+            }
+        }
+        matrix = next;
+    }
+    let cells = (n * n) as i32;
+    move |x, y| (matrix[y % n][x % n] as i32 * 2 + 1) * 256 / (2 * cells) - 1
+}
+
+/// Error diffusion, keeping the error of the rows the kernel reaches.
+fn diffuse(
+    bitmap: &mut Bitmap,
+    level: impl Fn(usize, usize) -> u8,
+    (kernel, divisor): (&[(i64, usize, i32)], i32),
+) {
+    let width = bitmap.width() as usize;
+    let height = bitmap.height() as usize;
+    let depth = kernel.iter().map(|&(_, dy, _)| dy).max().unwrap_or(0) + 1;
+    // 現在行から下へdepth行分の誤差
+    let mut errors = vec![vec![0i32; width]; depth];
+
+    for y in 0..height {
+        for x in 0..width {
+            let value = level(x, y) as i32 + errors[0][x];
+            let printed = value < 128;
+            if printed {
+                bitmap.set(x as u32, y as u32, true);
+            }
+            let error = value - if printed { 0 } else { 255 };
+            for &(dx, dy, weight) in kernel {
+                let tx = x as i64 + dx;
+                if tx >= 0 && (tx as usize) < width {
+                    errors[dy][tx as usize] += error * weight / divisor;
+                }
+            }
+        }
+        errors.rotate_left(1);
+        for error in errors[depth - 1].iter_mut() {
+            *error = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{step_filter_normal, step_filter_wide, Matrix, NORMAL_PRINTER_WIDTH, WIDE_PRINTER_WIDTH};
+
+    fn coverage(bitmap: &Bitmap) -> f64 {
+        let printed = (0..bitmap.height())
+            .flat_map(|y| (0..bitmap.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| bitmap.get(x, y) == Some(true))
+            .count();
+        printed as f64 / (bitmap.width() * bitmap.height()) as f64
+    }
+
+    #[test]
+    fn test_threshold_matches_step_filter() {
+        let (width, height) = (NORMAL_PRINTER_WIDTH, 4);
+        let gray: Vec<u8> = (0..width * height).map(|i| (i * 13 % 256) as u8).collect();
+        let bitmap = dither(Dither::Threshold(80), width, height, &gray, false).unwrap();
+        assert_eq!(Matrix::from(bitmap), step_filter_normal(80, height, gray));
+    }
+
+    #[test]
+    fn test_mid_gray_prints_half_the_dots() {
+        let (width, height) = (64, 64);
+        let gray = vec![128u8; (width * height) as usize];
+        for method in [
+            Dither::FloydSteinberg,
+            Dither::Atkinson,
+            Dither::Stucki,
+            Dither::Bayer4,
+            Dither::Bayer8,
+        ]
+        .iter()
+        {
+            let bitmap = dither(*method, width, height, &gray, false).unwrap();
+            let coverage = coverage(&bitmap);
+            assert!((0.4..=0.6).contains(&coverage), "{:?}: {}", method, coverage);
+        }
+
+        // 白と黒は点を打たない/全て打つ
+        for method in [Dither::FloydSteinberg, Dither::Bayer8].iter() {
+            let white = dither(*method, width, height, &vec![255; gray.len()], false).unwrap();
+            let black = dither(*method, width, height, &vec![0; gray.len()], false).unwrap();
+            assert_eq!((coverage(&white), coverage(&black)), (0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_high_resolution_doubles_rows() {
+        let gray: Vec<u8> = (0..16 * 8).map(|i| (i * 2) as u8).collect();
+        let bitmap = dither(Dither::Bayer4, 16, 8, &gray, true).unwrap();
+        assert_eq!((bitmap.width(), bitmap.height(), bitmap.dpi()), (16, 16, (300, 600)));
+
+        assert!(matches!(
+            dither(Dither::Bayer4, 16, 9, &gray, false),
+            Err(Error::InvalidBitmap(_))
+        ));
+    }
+
+    #[test]
+    fn test_wide_conversion() {
+        let (width, height) = (WIDE_PRINTER_WIDTH, 3);
+        let gray: Vec<u8> = (0..width * height).map(|i| (i * 7 % 256) as u8).collect();
+        let bitmap = dither(Dither::Threshold(100), width, height, &gray, false).unwrap();
+        let matrix = Matrix::from(bitmap);
+        assert!(matrix.iter().all(|row| row.len() == 162));
+        assert_eq!(matrix, step_filter_wide(100, height, gray));
+    }
+
+    #[test]
+    fn test_oversized_dimensions_rejected() {
+        // u32で掛けると桁あふれして一致してしまう大きさ
+        assert!(matches!(
+            dither(Dither::Bayer4, 1 << 16, 1 << 16, &[], false),
+            Err(Error::InvalidBitmap(_))
+        ));
+        // 幅0でも高解像度の行数が桁あふれする
+        assert!(matches!(
+            dither(Dither::Bayer4, 0, u32::MAX, &[], true),
+            Err(Error::InvalidBitmap(_))
+        ));
+    }
+}
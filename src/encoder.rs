@@ -0,0 +1,517 @@
+//! Raster command encoder.
+//!
+//! `RasterEncoder` produces the exact byte stream `Printer` sends to the
+//! device, without needing a device. The output can be written to a `.bin`
+//! file and sent to the printer later, e.g. with `cat job.bin > /dev/usb/lp0`.
+
+use log::{debug, warn};
+use std::io::Write;
+
+use crate::{
+    error::Error,
+    media::Media,
+    printer::Config,
+    rows::{Row, RowSource, Rows},
+    Matrix,
+};
+
+/// Size of the chunks a streamed page is written in.
+pub(crate) const CHUNK_SIZE: usize = 16 * 1024;
+
+// エンコード済みのバイト列の送り先
+type Sink<'a> = &'a mut dyn FnMut(&[u8]) -> Result<(), Error>;
+
+/// Invalidate followed by ESC @ (initialize).
+pub(crate) fn initialize() -> Vec<u8> {
+    let mut buf: Vec<u8> = Vec::new();
+    buf.append(&mut [0x00; 400].to_vec());
+    buf.append(&mut [0x1B, 0x40].to_vec());
+    buf
+}
+
+/// Encoder turning a `Config` and bitmap pages into raster commands.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{Config, ContinuousType, Media, Model, RasterEncoder};
+/// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+///                          Media::Continuous(ContinuousType::Continuous62));
+/// let encoder = RasterEncoder::new(config)?;
+///
+/// let page = vec![vec![0xFF; 90]; 300];
+/// let mut file = Vec::new(); // or std::fs::File::create("label.bin")?
+/// encoder.write_to(&mut file, vec![page])?;
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct RasterEncoder {
+    config: Config,
+    compression: bool,
+}
+
+impl RasterEncoder {
+    /// Create an encoder for the given configuration.
+    ///
+    /// # Returns
+    /// * `Ok(RasterEncoder)` - Configuration is valid
+    /// * `Err(Error::InvalidConfig)` - Configuration values are out of range
+    pub fn new(config: Config) -> Result<Self, Error> {
+        config.validate()?;
+
+        // QL-800などでは圧縮モードがサポートされていないため、常に非圧縮とする
+        let compression = if !config.model.capabilities().compression && config.compress {
+            warn!(
+                "{} does not support compression mode, using uncompressed mode instead",
+                config.model
+            );
+            false
+        } else {
+            config.compress
+        };
+
+        Ok(RasterEncoder {
+            config,
+            compression,
+        })
+    }
+
+    /// Encode a whole job into a byte vector.
+    ///
+    /// Pages are separated by FF and the last one is terminated by Control-Z,
+    /// the same as `Printer::print` sends them.
+    pub fn encode(&self, pages: impl IntoIterator<Item = Matrix>) -> Result<Vec<u8>, Error> {
+        let mut buf = Vec::new();
+        self.write_to(&mut buf, pages)?;
+        Ok(buf)
+    }
+
+    /// Encode a whole job and write it to `writer`, one page at a time.
+    pub fn write_to<W: Write>(
+        &self,
+        writer: &mut W,
+        pages: impl IntoIterator<Item = Matrix>,
+    ) -> Result<(), Error> {
+        self.write_rows(writer, pages.into_iter().map(Rows::from))
+    }
+
+    /// Encode a whole job of `RowSource` pages and write it to `writer`.
+    ///
+    /// Rows are pulled as they are encoded and written in chunks of about
+    /// 16 KiB, so memory use does not grow with the label length.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use ql_label::{Config, ContinuousType, Media, Model, RasterEncoder, Rows};
+    /// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    ///                          Media::Continuous(ContinuousType::Continuous62));
+    /// let encoder = RasterEncoder::new(config)?;
+    ///
+    /// let banner = Rows::new(20000, (0..20000).map(|_| vec![0xFF; 90]));
+    /// let mut file = Vec::new(); // or std::fs::File::create("banner.bin")?
+    /// encoder.write_rows(&mut file, vec![banner])?;
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn write_rows<W: Write, R: RowSource>(
+        &self,
+        writer: &mut W,
+        pages: impl IntoIterator<Item = R>,
+    ) -> Result<(), Error> {
+        let mut iter = pages.into_iter().peekable();
+        let mut first = true;
+        let mut buf = Vec::with_capacity(CHUNK_SIZE);
+
+        while let Some(rows) = iter.next() {
+            if first {
+                buf.append(&mut self.preamble()?);
+            }
+            let last = iter.peek().is_none();
+            self.stream_page(&mut buf, rows, first, last, CHUNK_SIZE, &mut |chunk| {
+                writer.write_all(chunk).map_err(Error::from)
+            })?;
+            first = false;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Job header sent once before the first page.
+    pub(crate) fn preamble(&self) -> Result<Vec<u8>, Error> {
+        let mut preamble: Vec<u8> = initialize();
+        preamble.append(&mut [0x1B, 0x69, 0x61, 0x01].to_vec()); // Set raster command mode
+        if self.config.model.capabilities().auto_status {
+            preamble.append(&mut [0x1B, 0x69, 0x21, 0x00].to_vec()); // Set auto status notificatoin mode
+        }
+
+        // Apply config values
+        preamble.append(&mut self.config.clone().build()?);
+
+        if self.compression {
+            preamble.append(&mut [0x4D, 0x02].to_vec()); // Set to pack bits compression mode
+        } else {
+            preamble.append(&mut [0x4D, 0x00].to_vec()); // Set to no compression mode
+        }
+
+        debug!("{:?}", self.config);
+        Ok(preamble)
+    }
+
+    /// Append the print information, raster lines and print command of one page.
+    pub(crate) fn page(&self, buf: &mut Vec<u8>, image: Matrix, first: bool, last: bool) -> Result<(), Error> {
+        // 一括で組み立てるのでチャンクに分けない
+        self.encode_page(buf, Rows::from(image), first, last, None)
+    }
+
+    /// Encode one page from `rows`, handing the bytes to `sink` in chunks.
+    ///
+    /// `buf` is sent ahead of the page, e.g. the preamble. A chunk is handed
+    /// over whenever `buf` reaches `chunk_size` bytes and the rest once the
+    /// page is complete, so at most about `chunk_size` bytes are held at once.
+    pub(crate) fn stream_page(
+        &self,
+        buf: &mut Vec<u8>,
+        rows: impl RowSource,
+        first: bool,
+        last: bool,
+        chunk_size: usize,
+        sink: Sink<'_>,
+    ) -> Result<(), Error> {
+        self.encode_page(buf, rows, first, last, Some((chunk_size, &mut *sink)))?;
+        sink(buf)?;
+        buf.clear();
+        Ok(())
+    }
+
+    /// Append one page to `buf`, flushing full chunks to the sink if there is one.
+    fn encode_page(
+        &self,
+        buf: &mut Vec<u8>,
+        mut rows: impl RowSource,
+        first: bool,
+        last: bool,
+        mut sink: Option<(usize, Sink<'_>)>,
+    ) -> Result<(), Error> {
+        let mut flush = |buf: &mut Vec<u8>| -> Result<(), Error> {
+            if let Some((chunk_size, sink)) = sink.as_mut() {
+                if buf.len() >= *chunk_size {
+                    sink(buf)?;
+                    buf.clear();
+                }
+            }
+            Ok(())
+        };
+
+        // ESC i z 印刷情報司令
+        let count = rows.raster_count();
+        let raster_count = if self.config.two_colors { count / 2 } else { count };
+        self.set_media(buf, raster_count, first);
+
+        // Add raster line image data
+        let width = self.line_bytes();
+        let mut color = false;
+        for sent in 0..count {
+            let row = rows.next_row().ok_or_else(|| {
+                Error::InvalidRows(format!("source ended after {} of {} lines", sent, count))
+            })?;
+            if row.len() != width {
+                return Err(Error::InvalidRows(format!(
+                    "line {} is {} bytes, {:?} needs {}",
+                    sent,
+                    row.len(),
+                    self.config.model,
+                    width
+                )));
+            }
+            self.raster_line(buf, row, &mut color);
+            flush(buf)?;
+        }
+        if rows.next_row().is_some() {
+            return Err(Error::InvalidRows(format!("source yields more than {} lines", count)));
+        }
+
+        if last {
+            buf.push(0x1A); // Control-Z : Print then Eject
+        } else {
+            buf.push(0x0C); // FF : Print
+        }
+        Ok(())
+    }
+
+    /// Append one raster line, `color` alternates between red and black in two-color mode.
+    fn raster_line(&self, buf: &mut Vec<u8>, mut row: Row, color: &mut bool) {
+        let width = self.line_bytes();
+        if self.config.two_colors {
+            if *color {
+                // Black raster line (color code 0x01)
+                buf.extend_from_slice(&[0x77, 0x01, width as u8]);
+            } else {
+                // Red raster line (color code 0x02)
+                buf.extend_from_slice(&[0x77, 0x02, width as u8]);
+            }
+            buf.append(&mut row);
+            *color = !*color;
+        } else if self.compression {
+            let mut packed = pack_bits(&row);
+            let len = packed.len() as u8;
+            buf.extend_from_slice(&[0x67, 0x00, len]);
+            buf.append(&mut packed);
+        } else {
+            buf.extend_from_slice(&[0x67, 0x00, width as u8]);
+            buf.append(&mut row);
+        }
+    }
+
+    /// Bytes per raster line, 90 for normal and 162 for wide models.
+    fn line_bytes(&self) -> usize {
+        self.config.model.capabilities().line_bytes()
+    }
+
+    fn set_media(&self, buf: &mut Vec<u8>, raster_count: u32, first: bool) {
+        buf.extend_from_slice(&[0x1B, 0x69, 0x7A]); // ESC i z
+
+        // n1: 有効フラグ (用紙種類+幅+長さ+ラスター数)
+        let valid_flags = 0x02 | 0x04 | 0x08 | 0x40;
+        buf.push(valid_flags);
+
+        // n2: 用紙種類 (長尺:0x0A, ダイカット:0x0C)
+        let media_type = match self.config.media {
+            Media::Continuous(_) => 0x0A,
+            Media::DieCut(_) => 0x0B,
+        };
+        buf.push(media_type);
+
+        // n3, n4: 用紙幅・長さ (mm)
+        let spec = self.config.media.spec();
+        buf.push(spec.width_mm());
+        buf.push(spec.length_mm());
+
+        // n5-n8: ラスター数 (リトルエンディアン)
+        let raster_bytes = raster_count.to_le_bytes();
+        buf.extend_from_slice(&raster_bytes);
+
+        // n9: 先頭ページフラグ (0=先頭ページ, 1=2ページ目以降)
+        buf.push(if first { 0x00 } else { 0x01 });
+
+        // n10: 固定値
+        buf.push(0x00);
+    }
+}
+
+/// TIFF PackBits圧縮アルゴリズム（Brother QL仕様準拠）
+///
+/// 仕様:
+/// - 同一データ連続：個数-1を負数で指定 + データ1バイト
+/// - 異なるデータ連続：個数-1を正数で指定 + 全データ
+/// - 圧縮後が元のサイズを超える場合は非圧縮として送信
+///   (90バイトなら91バイト、162バイトなら128バイトずつに分けて164バイト)
+pub(crate) fn pack_bits(data: &[u8]) -> Vec<u8> {
+    let mut packed = Vec::new();
+    let mut i = 0;
+
+    while i < data.len() {
+        // Run-length encoding (RLE)のチェック
+        let mut run_length = 1;
+        let run_value = data[i];
+
+        // 同じ値の連続をカウント（最大128個まで）
+        while i + run_length < data.len()
+            && run_length < 128
+            && data[i + run_length] == run_value
+        {
+            run_length += 1;
+        }
+
+        // RLEが効果的な場合（2個以上の連続）
+        if run_length >= 2 {
+            // 負数で圧縮指示: -(count-1)
+            packed.push((1 - run_length as i16) as u8);
+            packed.push(run_value);
+            i += run_length;
+        } else {
+            // リテラル実行のチェック
+            let start_pos = i;
+            let mut literal_length = 1;
+
+            // リテラル実行の最適な長さを決定
+            while i + literal_length < data.len() && literal_length < 128 {
+                // 次の位置で2個以上同じ値が続く場合は、ここでリテラル実行を終了
+                if i + literal_length + 1 < data.len()
+                    && data[i + literal_length] == data[i + literal_length + 1]
+                {
+                    break;
+                }
+                literal_length += 1;
+            }
+
+            // リテラル実行: 正数で非圧縮指示
+            packed.push((literal_length - 1) as u8);
+            packed.extend_from_slice(&data[start_pos..start_pos + literal_length]);
+            i += literal_length;
+        }
+    }
+
+    // 重要な最適化: 元のサイズ超過時は非圧縮のリテラル実行として返す
+    if packed.len() > data.len() {
+        warn!(
+            "Data compression ineffective, sending uncompressed ({} bytes)",
+            data.len()
+        );
+        let mut result = Vec::with_capacity(data.len() + data.len().div_ceil(128));
+        for chunk in data.chunks(128) {
+            result.push((chunk.len() - 1) as u8);
+            result.extend_from_slice(chunk);
+        }
+        result
+    } else {
+        debug!(
+            "Compression reduced data from {} to {} bytes ({:.1}% reduction)",
+            data.len(),
+            packed.len(),
+            (1.0 - packed.len() as f64 / data.len().max(1) as f64) * 100.0
+        );
+        packed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ContinuousType, DieCutType, Model};
+
+    #[test]
+    fn test_pack_bits_compression() {
+        // テスト1: 効果的な圧縮（同一データ連続）
+        let all_zeros = vec![0u8; 90];
+        let compressed = pack_bits(&all_zeros);
+        println!(
+            "All zeros: {} -> {} bytes",
+            all_zeros.len(),
+            compressed.len()
+        );
+        assert!(compressed.len() < all_zeros.len(), "圧縮が効果的でない");
+
+        // テスト2: 非効果的な圧縮（ランダムデータ）
+        let random_data: Vec<u8> = (0..90).map(|i| (i * 37 + 17) as u8).collect();
+        let compressed_random = pack_bits(&random_data);
+        println!(
+            "Random data: {} -> {} bytes",
+            random_data.len(),
+            compressed_random.len()
+        );
+
+        // テスト3: 91バイト制限の確認
+        if compressed_random.len() > 90 {
+            println!("91バイト制限により非圧縮データが返される");
+            assert_eq!(compressed_random.len(), 91); // 89 + 90バイトの元データ
+            assert_eq!(compressed_random[0], 89); // 非圧縮指示
+        }
+
+        // テスト4: 混合パターン（部分的な圧縮効果）
+        let mut mixed_data = vec![0u8; 30];
+        mixed_data.extend(vec![255u8; 30]);
+        mixed_data.extend((0..30).map(|i| i as u8));
+        let compressed_mixed = pack_bits(&mixed_data);
+        println!(
+            "Mixed data: {} -> {} bytes",
+            mixed_data.len(),
+            compressed_mixed.len()
+        );
+    }
+
+    #[test]
+    fn test_pack_bits_edge_cases() {
+        // エッジケース1: 空のデータ
+        let empty_data = vec![];
+        let compressed_empty = pack_bits(&empty_data);
+        assert_eq!(compressed_empty, empty_data);
+
+        // エッジケース2: 90バイト以外のサイズも圧縮する
+        let wide = vec![42u8; 162];
+        let compressed_wide = pack_bits(&wide);
+        assert_eq!(compressed_wide, vec![(-127i8) as u8, 42, (-33i8) as u8, 42]);
+
+        // 162バイトの非圧縮は128バイトと34バイトのリテラル実行に分ける
+        let random_wide: Vec<u8> = (0..162).map(|i| (i * 37 + 17) as u8).collect();
+        let compressed_random_wide = pack_bits(&random_wide);
+        assert_eq!(compressed_random_wide.len(), 164);
+        assert_eq!(compressed_random_wide[0], 127);
+        assert_eq!(compressed_random_wide[129], 33);
+
+        // エッジケース3: 単一バイトの繰り返し（最大圧縮）
+        let single_byte = vec![42u8; 90];
+        let compressed_single = pack_bits(&single_byte);
+        assert_eq!(compressed_single.len(), 2); // 長さ指示 + データ
+        assert_eq!(compressed_single[0], (-(90i8 - 1)) as u8); // -89
+        assert_eq!(compressed_single[1], 42);
+    }
+
+    #[test]
+    fn test_encode_golden_bytes() {
+        let config = Config::new(
+            Model::QL820NWB,
+            "serial".to_string(),
+            Media::DieCut(DieCutType::DieCut29x90),
+        );
+        let encoder = RasterEncoder::new(config).unwrap();
+        let page = vec![vec![0xAA; 90]; 2];
+        let job = encoder.encode(vec![page.clone(), page]).unwrap();
+
+        let mut expected = vec![0x00; 400];
+        expected.extend_from_slice(&[0x1B, 0x40]); // ESC @
+        expected.extend_from_slice(&[0x1B, 0x69, 0x61, 0x01]); // ESC i a
+        expected.extend_from_slice(&[0x1B, 0x69, 0x21, 0x00]); // ESC i !
+        expected.extend_from_slice(&[0x1B, 0x69, 0x64, 0x00, 0x00]); // ESC i d
+        expected.extend_from_slice(&[0x1B, 0x69, 0x4D, 0x40]); // ESC i M
+        expected.extend_from_slice(&[0x1B, 0x69, 0x41, 0x01]); // ESC i A
+        expected.extend_from_slice(&[0x1B, 0x69, 0x4B, 0x08]); // ESC i K
+        expected.extend_from_slice(&[0x4D, 0x00]); // M
+        for (n9, end) in [(0x00, 0x0C), (0x01, 0x1A)] {
+            // ESC i z: 先頭ページのみn9=0、その後に余分なバイトは続かない
+            expected.extend_from_slice(&[0x1B, 0x69, 0x7A, 0x4E, 0x0B, 29, 90]);
+            expected.extend_from_slice(&[0x02, 0x00, 0x00, 0x00, n9, 0x00]);
+            for _ in 0..2 {
+                expected.extend_from_slice(&[0x67, 0x00, 90]);
+                expected.extend_from_slice(&[0xAA; 90]);
+            }
+            expected.push(end);
+        }
+        assert_eq!(job, expected);
+    }
+
+    #[test]
+    fn test_encode_compressed_ql800_falls_back() {
+        let media = Media::Continuous(ContinuousType::Continuous29);
+        let config = Config::new(Model::QL800, "serial".to_string(), media).compress(true);
+        let job = RasterEncoder::new(config)
+            .unwrap()
+            .encode(vec![vec![vec![0x00; 90]; 1]])
+            .unwrap();
+        // 非圧縮モード指定と90バイトのラスター行
+        assert!(job.windows(5).any(|w| w == [0x4D, 0x00, 0x1B, 0x69, 0x7A]));
+        assert_eq!(&job[job.len() - 94..job.len() - 91], &[0x67, 0x00, 90]);
+    }
+
+    #[test]
+    fn test_encode_wide_lines() {
+        let media = Media::Continuous(ContinuousType::Continuous102);
+        let config = Config::new(Model::QL1100, "serial".to_string(), media);
+        let page = vec![vec![0x55; 162]; 3];
+        let job = RasterEncoder::new(config.clone())
+            .unwrap()
+            .encode(vec![page.clone()])
+            .unwrap();
+
+        let commands = crate::decode(&job).unwrap();
+        assert!(crate::lint(&commands, Model::QL1100).is_empty());
+        let lines = commands
+            .iter()
+            .filter(|c| **c == crate::RasterCommand::Raster(vec![0x55; 162]))
+            .count();
+        assert_eq!(lines, 3);
+
+        let job = RasterEncoder::new(config.compress(true))
+            .unwrap()
+            .encode(vec![page])
+            .unwrap();
+        assert!(crate::lint(&crate::decode(&job).unwrap(), Model::QL1100).is_empty());
+    }
+}
@@ -3,10 +3,12 @@
 //! This module defines all possible errors that can occur during printer
 //! communication, configuration, and print operations.
 
-use crate::Media;
-use rusb;
+use bitflags::bitflags;
+use std::fmt;
 use thiserror::Error;
 
+use crate::Media;
+
 /// Main error type for P-Touch printer operations.
 ///
 /// This enum encompasses all possible errors that can occur when using
@@ -20,6 +22,12 @@ pub enum Error {
     #[error(transparent)]
     UsbError(#[from] rusb::Error),
 
+    /// I/O error on a socket or device file.
+    ///
+    /// Wraps errors from transports which are not based on libusb.
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+
     /// Printer device is not connected or not responding.
     ///
     /// This error occurs when the printer cannot be found on USB or
@@ -30,6 +38,10 @@ pub enum Error {
     #[error("Can't read device list, permission issue ?")]
     DeviceListNotReadable,
 
+    /// libusb was built without hotplug support on this platform.
+    #[error("Hotplug is not supported")]
+    HotplugNotSupported,
+
     #[error("Device is missing endpoint")]
     MissingEndpoint,
 
@@ -40,7 +52,7 @@ pub enum Error {
     ///
     /// This error occurs when configuration values are out of range
     /// or incompatible with the selected printer model.
-    #[error("Invalid configuration parameter")]
+    #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
     #[error("No media is installed in the printer")]
@@ -53,6 +65,19 @@ pub enum Error {
     #[error("Media mismatch: expected {expected:?}, found {actual:?}")]
     MediaMismatch { expected: Media, actual: Media },
 
+    /// Raster stream ends inside a command.
+    ///
+    /// Holds the offset of the incomplete command.
+    #[error("Raster stream is truncated at offset {0}")]
+    TruncatedStream(usize),
+
+    /// Raster stream has an escape sequence the decoder does not know.
+    ///
+    /// Its parameter length is unknown, so the rest of the stream cannot be
+    /// decoded. Holds the offset of the sequence.
+    #[error("Unknown raster command at offset {0}")]
+    UnknownCommand(usize),
+
     #[error("Status request return no response")]
     ReadStatusTimeout,
 
@@ -64,101 +89,190 @@ pub enum Error {
     PrintTimeout,
 
     #[error("Unexpected printer phase: {0:?}")]
-    UnexpectedPhase(crate::printer::Phase),
-
-    /// Hardware-level printer error.
-    ///
-    /// Wraps printer-specific errors reported by the device itself,
-    /// such as cover open, media issues, or mechanical problems.
-    #[error(transparent)]
-    PrinterError(PrinterError),
-}
+    UnexpectedPhase(crate::status::Phase),
 
-/// Hardware-specific errors reported by the printer.
-///
-/// These errors are parsed from the printer's status response and indicate
-/// physical problems with the device that need user intervention.
-#[derive(Error, Debug)]
-pub enum PrinterError {
-    // Following errors are read from printer status
-    #[error("No media is installed")]
-    NoMedia,
+    /// The thread driving the printer has stopped, e.g. because it panicked.
+    #[error("Printer I/O thread has stopped")]
+    WorkerStopped,
 
-    #[error("End of media")]
-    EndOfMedia,
+    /// Bitmap data does not match its dimensions.
+    #[error("Invalid bitmap: {0}")]
+    InvalidBitmap(String),
 
-    #[error("Cutter jam")]
-    CutterJam,
+    /// Raster lines of a page do not match its raster count or the line width.
+    #[error("Invalid raster lines: {0}")]
+    InvalidRows(String),
 
-    #[error("Printer is in use")]
-    PrinterInUse,
+    /// No printer of a `PrinterPool` is loaded with the media, or all of them failed.
+    #[error("No printer available for {0:?}")]
+    NoPrinterAvailable(Media),
 
-    #[error("Printer if offline")]
-    PrinterOffline,
+    /// A file of the spool directory could not be parsed.
+    #[error("Spool file is corrupted: {0}")]
+    SpoolCorrupted(String),
 
-    #[error("Installed media is not match")]
-    InvalidMedia,
+    /// No job with this id is in the spool.
+    #[error("Spooled job {0} not found")]
+    JobNotFound(crate::spool::JobId),
 
-    #[error("Expansion buffer is full")]
-    BufferFull,
+    /// Print job was cancelled through a `CancelToken`.
+    ///
+    /// `completed` is the number of pages printed before the job stopped.
+    #[error("Print job cancelled after {completed} pages")]
+    Cancelled { completed: usize },
 
-    #[error("Communication error")]
-    CommunicationError,
+    /// Print job stopped before every page was printed.
+    ///
+    /// `printed` holds the indices of the pages which were printed, `source`
+    /// the error which stopped the job.
+    #[error("Print job interrupted after printing pages {printed:?}: {source}")]
+    JobInterrupted {
+        printed: Vec<usize>,
+        source: Box<Error>,
+    },
 
-    #[error("Cover is open")]
-    CoverOpen,
+    /// Hardware-level printer error.
+    ///
+    /// Every error condition reported by the device itself, such as cover
+    /// open, media issues, or mechanical problems.
+    #[error("Printer error: {0}")]
+    PrinterError(PrinterErrors),
+}
 
-    #[error("Media can not be fed")]
-    FeedMediaFail,
+bitflags! {
+    /// Hardware-specific errors reported by the printer.
+    ///
+    /// Every condition set in error information 1 and 2 of the status reply
+    /// (bytes 8 and 9), byte 8 in the low and byte 9 in the high bits. The
+    /// printer may report several at once, e.g. cover open together with a
+    /// communication error.
+    pub struct PrinterErrors: u16 {
+        // Error information 1
+        const NO_MEDIA = 0x0001;
+        const END_OF_MEDIA = 0x0002;
+        const CUTTER_JAM = 0x0004;
+        const PRINTER_IN_USE = 0x0010;
+        const PRINTER_TURNED_OFF = 0x0020;
+        const HIGH_VOLTAGE_ADAPTER = 0x0040;
+        const FAN_MOTOR_ERROR = 0x0080;
+        // Error information 2
+        const REPLACE_MEDIA = 0x0100;
+        const EXPANSION_BUFFER_FULL = 0x0200;
+        const COMMUNICATION_ERROR = 0x0400;
+        const COMMUNICATION_BUFFER_FULL = 0x0800;
+        const COVER_OPEN = 0x1000;
+        const CANCEL_KEY = 0x2000;
+        const FEEDING_ERROR = 0x4000;
+        const SYSTEM_ERROR = 0x8000;
+    }
+}
 
-    #[error("System error")]
-    SystemError,
+impl PrinterErrors {
+    /// Errors the hardware can not get out of without service or a power cycle
+    pub const FATAL: Self = Self::from_bits_truncate(
+        Self::CUTTER_JAM.bits
+            | Self::HIGH_VOLTAGE_ADAPTER.bits
+            | Self::FAN_MOTOR_ERROR.bits
+            | Self::SYSTEM_ERROR.bits,
+    );
 
-    #[error("Unknown error")]
-    UnknownError((u8, u8)),
-}
+    /// Errors an operator clears by loading media or closing the cover
+    pub const RECOVERABLE: Self = Self::from_bits_truncate(
+        Self::NO_MEDIA.bits | Self::END_OF_MEDIA.bits | Self::REPLACE_MEDIA.bits | Self::COVER_OPEN.bits,
+    );
 
-impl PrinterError {
-    /// Parse printer error from 32-byte status buffer.
-    ///
-    /// Analyzes bytes 8 and 9 of the printer status response to determine
-    /// the specific error condition reported by the hardware.
+    /// Parse printer errors from 32-byte status buffer.
     ///
     /// # Arguments
     /// * `buf` - 32-byte status response from printer
     ///
     /// # Returns
-    /// Parsed printer error or `UnknownError((0, 0))` if no error
+    /// Every error bit set in bytes 8 and 9, empty if there is no error
     pub fn from_buf(buf: [u8; 32]) -> Self {
-        let err_1 = buf[8];
-        let err_2 = buf[9];
-
-        match err_1 {
-            0b0000_0001 => Self::NoMedia,
-            0b0000_0010 => Self::EndOfMedia,
-            0b0000_0100 => Self::CutterJam,
-            0b0001_0000 => Self::PrinterInUse,
-            0b0010_0000 => Self::PrinterOffline,
-            _ => match err_2 {
-                0b0000_0001 => Self::InvalidMedia,
-                0b0000_0010 => Self::BufferFull,
-                0b0000_0100 => Self::CommunicationError,
-                0b0001_0000 => Self::CoverOpen,
-                0b0100_0000 => Self::FeedMediaFail,
-                0b1000_0000 => Self::SystemError,
-                _ => Self::UnknownError((err_1, err_2)),
-            },
+        Self::from_bits_truncate(u16::from_le_bytes([buf[8], buf[9]]))
+    }
+
+    /// Errors an operator can clear, like closing the cover or replacing the media.
+    pub fn recoverable(&self) -> Self {
+        *self & Self::RECOVERABLE
+    }
+
+    /// `true` if errors are set and every one of them is recoverable.
+    pub fn is_recoverable(&self) -> bool {
+        !self.is_empty() && Self::RECOVERABLE.contains(*self)
+    }
+
+    /// Errors which need service or a power cycle, like a cutter jam.
+    pub fn fatal(&self) -> Self {
+        *self & Self::FATAL
+    }
+
+    /// `true` if at least one fatal error is set.
+    pub fn is_fatal(&self) -> bool {
+        self.intersects(Self::FATAL)
+    }
+
+    fn messages(&self) -> Vec<&'static str> {
+        let all = [
+            (Self::NO_MEDIA, "no media"),
+            (Self::END_OF_MEDIA, "end of media"),
+            (Self::CUTTER_JAM, "cutter jam"),
+            (Self::PRINTER_IN_USE, "printer in use"),
+            (Self::PRINTER_TURNED_OFF, "printer turned off"),
+            (Self::HIGH_VOLTAGE_ADAPTER, "high-voltage adapter"),
+            (Self::FAN_MOTOR_ERROR, "fan motor error"),
+            (Self::REPLACE_MEDIA, "replace media"),
+            (Self::EXPANSION_BUFFER_FULL, "expansion buffer full"),
+            (Self::COMMUNICATION_ERROR, "communication error"),
+            (Self::COMMUNICATION_BUFFER_FULL, "communication buffer full"),
+            (Self::COVER_OPEN, "cover open"),
+            (Self::CANCEL_KEY, "cancel key"),
+            (Self::FEEDING_ERROR, "media cannot be fed"),
+            (Self::SYSTEM_ERROR, "system error"),
+        ];
+        all.iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, message)| *message)
+            .collect()
+    }
+}
+
+impl fmt::Display for PrinterErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            write!(f, "no error")
+        } else {
+            write!(f, "{}", self.messages().join(", "))
         }
     }
+}
 
-    /// Check if this represents a "no error" state.
-    ///
-    /// Returns `true` if the printer is reporting no error condition.
-    /// Used to distinguish between actual errors and normal status.
-    ///
-    /// # Returns
-    /// `true` if no error is present, `false` otherwise
-    pub fn is_no_error(&self) -> bool {
-        matches!(self, Self::UnknownError((0, 0)))
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simultaneous_errors() {
+        let mut buf = [0u8; 32];
+        buf[8] = 0x04; // cutter jam
+        buf[9] = 0x14; // cover open + communication error
+
+        let errors = PrinterErrors::from_buf(buf);
+        assert_eq!(
+            errors,
+            PrinterErrors::CUTTER_JAM | PrinterErrors::COVER_OPEN | PrinterErrors::COMMUNICATION_ERROR
+        );
+        assert!(errors.is_fatal());
+        assert_eq!(errors.fatal(), PrinterErrors::CUTTER_JAM);
+        assert_eq!(errors.recoverable(), PrinterErrors::COVER_OPEN);
+        assert!(!errors.is_recoverable());
+        assert!((PrinterErrors::COVER_OPEN | PrinterErrors::END_OF_MEDIA).is_recoverable());
+        assert!(!PrinterErrors::CANCEL_KEY.is_recoverable());
+        assert_eq!(errors.to_string(), "cutter jam, communication error, cover open");
+
+        assert!(PrinterErrors::from_buf([0u8; 32]).is_empty());
+        // 複合フラグはall()やDebug出力に含まれない
+        assert_eq!(PrinterErrors::all().bits().count_ones(), 15);
+        assert_eq!(format!("{:?}", PrinterErrors::CUTTER_JAM), "CUTTER_JAM");
     }
 }
@@ -0,0 +1,145 @@
+//! Progress of a print job.
+//!
+//! `Printer::print` and `Printer::print_two_color` block until the whole job
+//! is done. A `JobObserver` set with `Printer::set_observer` is told about
+//! every step on the way, e.g. to drive a progress bar.
+
+use std::sync::mpsc::{Sender, SyncSender};
+use std::time::Duration;
+
+use crate::error::PrinterErrors;
+
+/// Step of a print job, page indices start at 0.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobEvent {
+    /// The job started, `pages` is known when the image iterator reports an exact size
+    Started { pages: Option<usize> },
+    /// Raster data of the page was sent to the printer
+    PageSent { page: usize },
+    /// The printer finished printing the page
+    PagePrinted { page: usize },
+    /// The print head is too hot, printing pauses until it has cooled down
+    CoolingStarted,
+    /// The print head has cooled down, printing continues
+    CoolingFinished,
+    /// The job stopped at `page`
+    ///
+    /// `errors` holds the conditions reported by the printer, it is empty
+    /// when the job stopped for another reason such as a communication error.
+    Failed {
+        page: usize,
+        errors: PrinterErrors,
+        message: String,
+    },
+    /// The job was cancelled after `completed` pages
+    Cancelled { completed: usize },
+    /// Every page was printed
+    Completed { pages: usize, elapsed: Duration },
+}
+
+/// Result of a completed job, see `Printer::print_pipelined`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobReport {
+    /// Pages printed
+    pub pages: usize,
+    /// Time from the first page sent to the last page printed
+    pub elapsed: Duration,
+    /// Measured throughput
+    pub labels_per_minute: f64,
+}
+
+impl JobReport {
+    pub(crate) fn new(pages: usize, elapsed: Duration) -> Self {
+        let minutes = elapsed.as_secs_f64() / 60.0;
+        JobReport {
+            pages,
+            elapsed,
+            labels_per_minute: if minutes > 0.0 { pages as f64 / minutes } else { 0.0 },
+        }
+    }
+}
+
+/// Receiver of `JobEvent`s.
+///
+/// Implemented for closures and for channel senders, so either a callback
+/// or the receiving end of a channel can follow the job.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{ContinuousType, JobEvent, Media, Model, SimulatedPrinter};
+/// # let media = Media::Continuous(ContinuousType::Continuous62);
+/// # let (_, mut printer) = SimulatedPrinter::connect(Model::QL820NWB, media);
+/// let (sender, receiver) = std::sync::mpsc::channel();
+/// printer.set_observer(sender);
+/// printer.print(vec![vec![vec![0xFF; 90]; 10]; 3].into_iter())?;
+///
+/// let printed = receiver
+///     .try_iter()
+///     .filter(|event| matches!(event, JobEvent::PagePrinted { .. }))
+///     .count();
+/// assert_eq!(printed, 3);
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+pub trait JobObserver: Send + Sync {
+    fn on_event(&self, event: &JobEvent);
+}
+
+impl<F> JobObserver for F
+where
+    F: Fn(&JobEvent) + Send + Sync,
+{
+    fn on_event(&self, event: &JobEvent) {
+        self(event)
+    }
+}
+
+// 受信側が閉じられていても印刷は続ける
+impl JobObserver for Sender<JobEvent> {
+    fn on_event(&self, event: &JobEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+impl JobObserver for SyncSender<JobEvent> {
+    fn on_event(&self, event: &JobEvent) {
+        let _ = self.send(event.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fixture::{default_printer, pattern};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_job_events() {
+        let (device, mut printer) = default_printer();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        printer.set_observer(move |event: &JobEvent| sink.lock().unwrap().push(event.clone()));
+
+        printer.print(vec![pattern(2), pattern(3)].into_iter()).unwrap();
+        let mut received = std::mem::take(&mut *events.lock().unwrap());
+        assert!(matches!(received.pop(), Some(JobEvent::Completed { pages: 2, .. })));
+        assert_eq!(
+            received,
+            vec![
+                JobEvent::Started { pages: Some(2) },
+                JobEvent::PageSent { page: 0 },
+                JobEvent::PagePrinted { page: 0 },
+                JobEvent::PageSent { page: 1 },
+                JobEvent::PagePrinted { page: 1 },
+            ]
+        );
+
+        device.raise_errors(PrinterErrors::COVER_OPEN);
+        let _ = printer.print(vec![pattern(2)].into_iter());
+        let received = events.lock().unwrap();
+        assert!(matches!(
+            received.last(),
+            Some(JobEvent::Failed { page: 0, errors, .. }) if *errors == PrinterErrors::COVER_OPEN
+        ));
+    }
+}
@@ -0,0 +1,273 @@
+//! USB hotplug monitoring.
+//!
+//! `HotplugWatcher` runs libusb's event loop on a background thread and turns
+//! hotplug callbacks for Brother devices into `HotplugEvent`s carrying the same
+//! `DeviceInfo` as `Printer::discover`.
+
+use log::{debug, info, warn};
+use rusb::{Context, Device, HotplugBuilder, Registration, UsbContext};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{
+    error::Error,
+    transport::{DeviceInfo, UsbTransport, VENDOR_ID},
+};
+
+/// Printer arriving on or leaving the USB bus.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HotplugEvent {
+    Attached(DeviceInfo),
+    Detached(DeviceInfo),
+}
+
+enum RawEvent {
+    Arrived(Device<Context>),
+    Left(Device<Context>),
+}
+
+// libusb does not allow I/O from inside the callbacks, so devices are only
+// queued here and inspected by the watcher thread afterwards.
+struct Callback {
+    queue: Arc<Mutex<Vec<RawEvent>>>,
+}
+
+impl rusb::Hotplug<Context> for Callback {
+    fn device_arrived(&mut self, device: Device<Context>) {
+        self.queue.lock().unwrap().push(RawEvent::Arrived(device));
+    }
+
+    fn device_left(&mut self, device: Device<Context>) {
+        self.queue.lock().unwrap().push(RawEvent::Left(device));
+    }
+}
+
+/// Printers currently attached, by serial number.
+///
+/// Updated by the watcher thread before each event is sent, so waiting for a
+/// printer does not depend on the events queued in the channel.
+#[derive(Default)]
+struct Presence {
+    attached: Mutex<HashMap<String, DeviceInfo>>,
+    changed: Condvar,
+}
+
+impl Presence {
+    fn record(&self, event: &HotplugEvent) {
+        let mut attached = self.attached.lock().unwrap();
+        match event {
+            HotplugEvent::Attached(device) => {
+                attached.insert(device.serial.clone(), device.clone());
+            }
+            HotplugEvent::Detached(device) => {
+                attached.remove(&device.serial);
+            }
+        }
+        self.changed.notify_all();
+    }
+
+    fn wait_for(&self, serial: &str, timeout: Duration) -> Result<DeviceInfo, Error> {
+        let deadline = Instant::now() + timeout;
+        let mut attached = self.attached.lock().unwrap();
+        loop {
+            if let Some(device) = attached.get(serial) {
+                return Ok(device.clone());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining == Duration::ZERO {
+                return Err(Error::DeviceOffline);
+            }
+            attached = self.changed.wait_timeout(attached, remaining).unwrap().0;
+        }
+    }
+}
+
+/// Background watcher reporting printers as they are plugged and unplugged.
+///
+/// Printers already connected when the watcher starts are reported as
+/// `Attached` first. The thread stops when the watcher is dropped.
+///
+/// # Example
+/// ```rust,no_run
+/// # use ql_label::{HotplugEvent, HotplugWatcher};
+/// let watcher = HotplugWatcher::start()?;
+/// while let Ok(event) = watcher.events().recv() {
+///     match event {
+///         HotplugEvent::Attached(device) => println!("+ {:?} {}", device.model, device.serial),
+///         HotplugEvent::Detached(device) => println!("- {:?} {}", device.model, device.serial),
+///     }
+/// }
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+pub struct HotplugWatcher {
+    events: Receiver<HotplugEvent>,
+    presence: Arc<Presence>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HotplugWatcher {
+    /// Register the hotplug callback and start the event thread.
+    ///
+    /// # Returns
+    /// * `Ok(HotplugWatcher)` - Watcher is running
+    /// * `Err(Error::HotplugNotSupported)` - libusb has no hotplug support on this platform
+    pub fn start() -> Result<Self, Error> {
+        if !rusb::has_hotplug() {
+            return Err(Error::HotplugNotSupported);
+        }
+
+        let context = Context::new()?;
+        let queue = Arc::new(Mutex::new(Vec::new()));
+        let registration = HotplugBuilder::new()
+            .vendor_id(VENDOR_ID)
+            .enumerate(true)
+            .register(
+                &context,
+                Box::new(Callback {
+                    queue: queue.clone(),
+                }),
+            )?;
+
+        let (sender, events) = mpsc::channel();
+        let presence = Arc::new(Presence::default());
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let presence = presence.clone();
+            let stop = stop.clone();
+            thread::spawn(move || Self::run(context, registration, queue, sender, presence, stop))
+        };
+
+        Ok(HotplugWatcher {
+            events,
+            presence,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Channel receiving the hotplug events.
+    pub fn events(&self) -> &Receiver<HotplugEvent> {
+        &self.events
+    }
+
+    /// Block until the printer with `serial` is attached.
+    ///
+    /// Returns at once when the printer is attached now, a printer attached
+    /// and detached again since the watcher started is waited for. The events
+    /// of `events` are left untouched.
+    ///
+    /// # Returns
+    /// * `Ok(DeviceInfo)` - The printer is available again
+    /// * `Err(Error::DeviceOffline)` - It did not appear within `timeout`
+    pub fn wait_for(&self, serial: &str, timeout: Duration) -> Result<DeviceInfo, Error> {
+        debug!("Waiting for printer {}", serial);
+        self.presence.wait_for(serial, timeout)
+    }
+
+    fn run(
+        context: Context,
+        registration: Registration<Context>,
+        queue: Arc<Mutex<Vec<RawEvent>>>,
+        sender: Sender<HotplugEvent>,
+        presence: Arc<Presence>,
+        stop: Arc<AtomicBool>,
+    ) {
+        // Devices can not be opened any more once they left, so remember
+        // what was read when they arrived.
+        let mut known: HashMap<(u8, u8), DeviceInfo> = HashMap::new();
+
+        while !stop.load(Ordering::Relaxed) {
+            let pending = std::mem::take(&mut *queue.lock().unwrap());
+            for event in pending {
+                let event = match event {
+                    RawEvent::Arrived(device) => match UsbTransport::read_device_info(&device) {
+                        Some((device_info, _, _)) => {
+                            info!("Printer attached: {:?}", device_info);
+                            known.insert(
+                                (device_info.bus_number, device_info.address),
+                                device_info.clone(),
+                            );
+                            HotplugEvent::Attached(device_info)
+                        }
+                        None => continue,
+                    },
+                    RawEvent::Left(device) => {
+                        match known.remove(&(device.bus_number(), device.address())) {
+                            Some(device_info) => {
+                                info!("Printer detached: {:?}", device_info);
+                                HotplugEvent::Detached(device_info)
+                            }
+                            None => continue,
+                        }
+                    }
+                };
+                presence.record(&event);
+                if sender.send(event).is_err() {
+                    return;
+                }
+            }
+
+            if let Err(err) = context.handle_events(Some(Duration::from_millis(200))) {
+                warn!("Hotplug event handling failed: {:?}", err);
+                break;
+            }
+        }
+        drop(registration);
+    }
+}
+
+impl Drop for HotplugWatcher {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Model;
+
+    fn device(serial: &str, address: u8) -> DeviceInfo {
+        DeviceInfo {
+            model: Model::QL820NWB,
+            serial: serial.to_string(),
+            manufacturer: None,
+            product: None,
+            bus_number: 1,
+            address,
+        }
+    }
+
+    #[test]
+    fn test_wait_for_uses_current_presence() {
+        let presence = Arc::new(Presence::default());
+        // 起動時に列挙され、その後抜かれた
+        presence.record(&HotplugEvent::Attached(device("A", 1)));
+        presence.record(&HotplugEvent::Attached(device("B", 2)));
+        presence.record(&HotplugEvent::Detached(device("A", 1)));
+
+        assert!(matches!(
+            presence.wait_for("A", Duration::from_millis(50)),
+            Err(Error::DeviceOffline)
+        ));
+        assert_eq!(presence.wait_for("B", Duration::ZERO).unwrap(), device("B", 2));
+
+        let plugged = presence.clone();
+        let operator = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            plugged.record(&HotplugEvent::Attached(device("A", 3)));
+        });
+        let started = Instant::now();
+        assert_eq!(presence.wait_for("A", Duration::from_secs(5)).unwrap(), device("A", 3));
+        assert!(started.elapsed() < Duration::from_secs(1));
+        operator.join().unwrap();
+    }
+}
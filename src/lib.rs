@@ -5,7 +5,7 @@
 //! # Example
 //!
 //! ```rust,no_run
-//! use ptouch::{Config, ContinuousType, Media, Model, Printer};
+//! use ql_label::{Config, ContinuousType, Media, Model, Printer};
 //! 
 //! let media = Media::Continuous(ContinuousType::Continuous29);
 //! let model = Model::QL820NWB;
@@ -13,20 +13,56 @@
 //! let printer = Printer::new(config).unwrap();
 //! ```
 
+#[cfg(feature = "tokio")]
+mod async_printer;
+mod bitmap;
+mod cancel;
+mod decoder;
+mod dither;
+mod encoder;
 mod error;
+mod event;
+mod hotplug;
 mod media;
 mod model;
+mod pool;
 mod printer;
+mod rows;
+mod shared;
+mod simulator;
+mod spool;
+mod status;
+mod transport;
 mod utils;
 
 pub use crate::{
-    error::{Error, PrinterError},
+    bitmap::{Bitmap, BitmapRows},
+    cancel::CancelToken,
+    decoder::{decode, lint, Lint, PrintInformation, RasterCommand, RasterDecoder},
+    dither::{dither, Dither},
+    encoder::RasterEncoder,
+    error::{Error, PrinterErrors},
+    event::{JobEvent, JobObserver, JobReport},
+    hotplug::{HotplugEvent, HotplugWatcher},
     media::{ContinuousType, DieCutType, Media},
-    model::Model,
-    printer::{Config, Printer, Status},
+    model::{Capabilities, InvalidPrinterName, Model, ReportedModel},
+    pool::{PrinterPool, PrinterUsage},
+    printer::{Config, Printer, RecoveryPolicy},
+    rows::{Row, RowSource, Rows},
+    shared::{JobHandle, SharedPrinter},
+    simulator::SimulatedPrinter,
+    spool::{JobId, JobState, SpoolJob, Spooler},
+    status::{MediaType, Notification, Phase, Status, StatusType},
+    transport::{DeviceInfo, TcpTransport, Transport, UsbTransport, RAW_PORT, STATUS_SIZE},
     utils::{convert_rgb_to_two_color, step_filter_normal, step_filter_wide, TwoColorMatrix},
 };
 
+#[cfg(target_os = "linux")]
+pub use crate::transport::UsblpTransport;
+
+#[cfg(feature = "tokio")]
+pub use crate::async_printer::{AsyncPrinter, StatusStream};
+
 /// Type alias for 1-bit bitmap data used by printers.
 ///
 /// Each inner `Vec<u8>` represents a single row of pixels, with 8 pixels
@@ -13,6 +13,8 @@ pub enum ContinuousType {
     Continuous54,
     Continuous62,
     Continuous62Red,
+    Continuous102,
+    Continuous103,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -32,6 +34,9 @@ pub enum DieCutType {
     DieCut12Dia,
     DieCut24Dia,
     DieCut58Dia,
+    DieCut102x51,
+    DieCut102x152,
+    DieCut103x164,
 }
 
 #[allow(dead_code)]
@@ -71,9 +76,45 @@ impl MediaSpec {
     pub fn length_mm(&self) -> u8 {
         self.length.mm
     }
+
+    /// Dots from the left edge of the print head to the right edge of the printable area.
+    pub fn required_pins(&self) -> u32 {
+        self.width.left + self.width.effective
+    }
 }
 
 impl Media {
+    /// Every media known to this crate.
+    pub const ALL: [Media; 27] = [
+        Media::Continuous(ContinuousType::Continuous12),
+        Media::Continuous(ContinuousType::Continuous29),
+        Media::Continuous(ContinuousType::Continuous38),
+        Media::Continuous(ContinuousType::Continuous50),
+        Media::Continuous(ContinuousType::Continuous54),
+        Media::Continuous(ContinuousType::Continuous62),
+        Media::Continuous(ContinuousType::Continuous62Red),
+        Media::Continuous(ContinuousType::Continuous102),
+        Media::Continuous(ContinuousType::Continuous103),
+        Media::DieCut(DieCutType::DieCut17x54),
+        Media::DieCut(DieCutType::DieCut17x87),
+        Media::DieCut(DieCutType::DieCut23x23),
+        Media::DieCut(DieCutType::DieCut29x42),
+        Media::DieCut(DieCutType::DieCut29x90),
+        Media::DieCut(DieCutType::DieCut38x90),
+        Media::DieCut(DieCutType::DieCut39x48),
+        Media::DieCut(DieCutType::DieCut52x29),
+        Media::DieCut(DieCutType::DieCut54x29),
+        Media::DieCut(DieCutType::DieCut60x86),
+        Media::DieCut(DieCutType::DieCut62x29),
+        Media::DieCut(DieCutType::DieCut62x100),
+        Media::DieCut(DieCutType::DieCut12Dia),
+        Media::DieCut(DieCutType::DieCut24Dia),
+        Media::DieCut(DieCutType::DieCut58Dia),
+        Media::DieCut(DieCutType::DieCut102x51),
+        Media::DieCut(DieCutType::DieCut102x152),
+        Media::DieCut(DieCutType::DieCut103x164),
+    ];
+
     pub fn spec(&self) -> MediaSpec {
         match self {
             Self::Continuous(t) => match t {
@@ -161,6 +202,31 @@ impl Media {
                     margin: MediaSize { mm: 1.5, dots: 18 },
                     offset: None,
                 },
+                // 以下はQL-1100シリーズ専用の幅広メディア
+                ContinuousType::Continuous102 => MediaSpec {
+                    id: 260,
+                    width: Width {
+                        mm: 102,
+                        left: 120,
+                        effective: 1164,
+                        right: 12,
+                    },
+                    length: Length { mm: 0, dots: 0 },
+                    margin: MediaSize { mm: 1.5, dots: 18 },
+                    offset: None,
+                },
+                ContinuousType::Continuous103 => MediaSpec {
+                    id: 265,
+                    width: Width {
+                        mm: 104,
+                        left: 88,
+                        effective: 1200,
+                        right: 8,
+                    },
+                    length: Length { mm: 0, dots: 0 },
+                    margin: MediaSize { mm: 1.5, dots: 18 },
+                    offset: None,
+                },
             },
             Self::DieCut(t) => match t {
                 DieCutType::DieCut17x54 => MediaSpec {
@@ -346,10 +412,59 @@ impl Media {
                     margin: MediaSize { mm: 3.0, dots: 35 },
                     offset: Some(MediaSize { mm: 3.0, dots: 35 }),
                 },
+                DieCutType::DieCut102x51 => MediaSpec {
+                    id: 365,
+                    width: Width {
+                        mm: 102,
+                        left: 120,
+                        effective: 1164,
+                        right: 12,
+                    },
+                    length: Length { mm: 51, dots: 602 },
+                    margin: MediaSize { mm: 1.5, dots: 18 },
+                    offset: Some(MediaSize { mm: 3.0, dots: 35 }),
+                },
+                DieCutType::DieCut102x152 => MediaSpec {
+                    id: 366,
+                    width: Width {
+                        mm: 102,
+                        left: 120,
+                        effective: 1164,
+                        right: 12,
+                    },
+                    length: Length {
+                        mm: 152,
+                        dots: 1795,
+                    },
+                    margin: MediaSize { mm: 1.5, dots: 18 },
+                    offset: Some(MediaSize { mm: 3.0, dots: 35 }),
+                },
+                DieCutType::DieCut103x164 => MediaSpec {
+                    id: 385,
+                    width: Width {
+                        mm: 104,
+                        left: 88,
+                        effective: 1200,
+                        right: 8,
+                    },
+                    length: Length {
+                        mm: 164,
+                        dots: 1937,
+                    },
+                    margin: MediaSize { mm: 1.5, dots: 18 },
+                    offset: Some(MediaSize { mm: 3.0, dots: 35 }),
+                },
             },
         }
     }
 
+    /// Identifier of the media, see `from_id`.
+    ///
+    /// Red/black tape shares the identifier of the black tape of the same width.
+    pub fn id(&self) -> u16 {
+        self.spec().id
+    }
+
     pub fn from_id(id: u16) -> Option<Self> {
         match id {
             // Document says it is 0x4A but actual value seems to be 0x0A
@@ -359,6 +474,8 @@ impl Media {
             262 => Some(Self::Continuous(ContinuousType::Continuous50)),
             261 => Some(Self::Continuous(ContinuousType::Continuous54)),
             259 => Some(Self::Continuous(ContinuousType::Continuous62)),
+            260 => Some(Self::Continuous(ContinuousType::Continuous102)),
+            265 => Some(Self::Continuous(ContinuousType::Continuous103)),
             //   0x81 => Some(Self::Continuous(ContinuousType::Continuous62Red)),
             // Same as above, 0x0B not 0x4B
             269 => Some(Self::DieCut(DieCutType::DieCut17x54)),
@@ -376,6 +493,9 @@ impl Media {
             362 => Some(Self::DieCut(DieCutType::DieCut12Dia)),
             363 => Some(Self::DieCut(DieCutType::DieCut24Dia)),
             273 => Some(Self::DieCut(DieCutType::DieCut58Dia)),
+            365 => Some(Self::DieCut(DieCutType::DieCut102x51)),
+            366 => Some(Self::DieCut(DieCutType::DieCut102x152)),
+            385 => Some(Self::DieCut(DieCutType::DieCut103x164)),
             _ => None,
         }
     }
@@ -390,7 +510,7 @@ impl Media {
     pub fn check_feed_value(&self, feed: u16) -> Result<[u8; 2], String> {
         match self {
             Self::Continuous(_) => {
-                if feed < 35 || feed > 1500 {
+                if !(35..=1500).contains(&feed) {
                     Err(format!("Feed value {} is out range.", feed))
                 } else {
                     Ok(feed.to_le_bytes())
@@ -444,6 +564,9 @@ impl Media {
                     0x81 => Some(Self::Continuous(ContinuousType::Continuous62Red)),
                     _ => None,
                 },
+                102 => Some(Self::Continuous(ContinuousType::Continuous102)),
+                // 103mm幅のテープは104mmとして報告される
+                104 => Some(Self::Continuous(ContinuousType::Continuous103)),
                 _ => None,
             },
             0x0B => match (w, l) {
@@ -463,6 +586,9 @@ impl Media {
                 (12, 12) => Some(Self::DieCut(DieCutType::DieCut12Dia)),
                 (24, 24) => Some(Self::DieCut(DieCutType::DieCut24Dia)),
                 (58, 58) => Some(Self::DieCut(DieCutType::DieCut58Dia)),
+                (102, 51) => Some(Self::DieCut(DieCutType::DieCut102x51)),
+                (102, 152) => Some(Self::DieCut(DieCutType::DieCut102x152)),
+                (104, 164) => Some(Self::DieCut(DieCutType::DieCut103x164)),
                 _ => None,
             },
             _ => None,
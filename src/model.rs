@@ -1,6 +1,9 @@
+use std::fmt;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Copy)]
+use crate::media::{ContinuousType, Media};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Model {
     QL500,
     QL550,
@@ -25,46 +28,124 @@ pub enum Model {
 #[derive(Debug, PartialEq, Eq)]
 pub struct InvalidPrinterName;
 
+// (モデル, ステータスのモデルコード, USB PID, 製品名)
+// QL-500とQL-550は同じモデルコードを返す
+const MODELS: [(Model, u8, u16, &str); 18] = [
+    (Model::QL500, 0x4F, 0x2015, "QL-500"),
+    (Model::QL550, 0x4F, 0x2016, "QL-550"),
+    (Model::QL560, 0x31, 0x2027, "QL-560"),
+    (Model::QL570, 0x32, 0x2028, "QL-570"),
+    (Model::QL580N, 0x33, 0x2029, "QL-580N"),
+    (Model::QL600, 0x47, 0x20C0, "QL-600"),
+    (Model::QL650TD, 0x51, 0x201B, "QL-650TD"),
+    (Model::QL700, 0x35, 0x2042, "QL-700"),
+    (Model::QL710W, 0x36, 0x2043, "QL-710W"),
+    (Model::QL720NW, 0x37, 0x2044, "QL-720NW"),
+    (Model::QL800, 0x38, 0x209B, "QL-800"),
+    (Model::QL810W, 0x39, 0x209C, "QL-810W"),
+    (Model::QL820NWB, 0x41, 0x209D, "QL-820NWB"),
+    (Model::QL1050, 0x50, 0x2020, "QL-1050"),
+    (Model::QL1060N, 0x34, 0x202A, "QL-1060N"),
+    (Model::QL1100, 0x43, 0x20A7, "QL-1100"),
+    (Model::QL1110NWB, 0x44, 0x20A8, "QL-1110NWB"),
+    (Model::QL1115NWB, 0x45, 0x20AB, "QL-1115NWB"),
+];
+
 impl FromStr for Model {
     type Err = InvalidPrinterName;
 
+    /// Parse a product name such as `QL-820NWB`.
+    ///
+    /// Case, spaces and dashes are ignored, so the USB product string and the
+    /// variant name (`QL820NWB`) are accepted as well.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "QL-720NW" => Ok(Self::QL720NW),
-            "QL-800" => Ok(Self::QL800),
-            "QL-820NW" => Ok(Self::QL820NWB),
-            "QL-820NWB" => Ok(Self::QL820NWB),
-            _ => Err(InvalidPrinterName),
+        let normalize = |s: &str| -> String {
+            s.chars()
+                .filter(|c| !matches!(c, '-' | ' ' | '_'))
+                .flat_map(char::to_uppercase)
+                .collect()
+        };
+        let name = normalize(s);
+        // 旧名称
+        if name == "QL820NW" {
+            return Ok(Self::QL820NWB);
         }
+        MODELS
+            .iter()
+            .find(|(_, _, _, n)| normalize(n) == name)
+            .map(|(model, _, _, _)| *model)
+            .ok_or(InvalidPrinterName)
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
     }
 }
+
 impl Model {
-    pub fn from_code(code: u8) -> Self {
-        match code {
-            0x47 => Self::QL600,
-            0x37 => Self::QL720NW,
-            0x38 => Self::QL800,
-            0x39 => Self::QL810W,
-            0x41 => Self::QL820NWB,
-            0x43 => Self::QL1100,
-            0x44 => Self::QL1110NWB,
-            0x45 => Self::QL1115NWB,
-            _ => panic!("Unknown model code {}", code),
-        }
+    /// Every supported model.
+    pub const ALL: [Model; 18] = [
+        Model::QL500,
+        Model::QL550,
+        Model::QL560,
+        Model::QL570,
+        Model::QL580N,
+        Model::QL600,
+        Model::QL650TD,
+        Model::QL700,
+        Model::QL710W,
+        Model::QL720NW,
+        Model::QL800,
+        Model::QL810W,
+        Model::QL820NWB,
+        Model::QL1050,
+        Model::QL1060N,
+        Model::QL1100,
+        Model::QL1110NWB,
+        Model::QL1115NWB,
+    ];
+
+    fn entry(&self) -> &'static (Model, u8, u16, &'static str) {
+        MODELS
+            .iter()
+            .find(|(model, _, _, _)| model == self)
+            .expect("every model is listed in MODELS")
+    }
+
+    /// Look up the model from the model code in byte 4 of the status reply.
+    ///
+    /// QL-500 and QL-550 report the same code, `QL550` is returned for it.
+    pub fn from_code(code: u8) -> Option<Self> {
+        MODELS
+            .iter()
+            .rev()
+            .find(|(_, c, _, _)| *c == code)
+            .map(|(model, _, _, _)| *model)
+    }
+
+    /// Model code reported in byte 4 of the status reply.
+    pub fn code(&self) -> u8 {
+        self.entry().1
+    }
+
+    /// Look up the model from its USB product id.
+    pub fn from_pid(pid: u16) -> Option<Self> {
+        MODELS
+            .iter()
+            .find(|(_, _, p, _)| *p == pid)
+            .map(|(model, _, _, _)| *model)
     }
 
+    /// USB product id.
     pub fn pid(&self) -> u16 {
-        match self {
-            Self::QL600 => 0x20C0,
-            Self::QL720NW => 0x2044,
-            Self::QL800 => 0x209b,
-            Self::QL810W => 0x209c,
-            Self::QL820NWB => 0x209d,
-            Self::QL1100 => 0x20A7,
-            Self::QL1110NWB => 0x20A8,
-            Self::QL1115NWB => 0x20AB,
-            _ => 0x0000,
-        }
+        self.entry().2
+    }
+
+    /// Product name as printed on the device, e.g. `QL-820NWB`.
+    pub fn name(&self) -> &'static str {
+        self.entry().3
     }
 
     pub fn pins(&self) -> u32 {
@@ -78,11 +159,155 @@ impl Model {
         }
     }
 
-    // pub fn supported_medias(&self) -> Vec<Media> {
-    //     match self {
-    //         Self::QL800 => vec![Media::Continuous29],
-    //         Self::QL810W => vec![Media::Continuous29],
-    //         Self::QL820NWB => vec![Media::Continuous29],
-    //     }
-    // }
+    /// What the model supports.
+    ///
+    /// # Example
+    /// ```rust
+    /// # use ql_label::{ContinuousType, Media, Model};
+    /// let capabilities = Model::QL800.capabilities();
+    /// assert!(!capabilities.compression);
+    /// assert!(capabilities.two_colors);
+    /// assert!(!capabilities.supports_media(Media::Continuous(ContinuousType::Continuous102)));
+    /// ```
+    pub fn capabilities(&self) -> Capabilities {
+        use Model::*;
+
+        Capabilities {
+            compression: !matches!(self, QL500 | QL550 | QL560 | QL570 | QL600 | QL700 | QL800),
+            two_colors: matches!(self, QL800 | QL810W | QL820NWB),
+            high_resolution: !matches!(self, QL500 | QL550 | QL560 | QL650TD | QL1050 | QL1060N),
+            // QL-500は手動カッターのみ
+            cutter: !matches!(self, QL500),
+            auto_status: !matches!(
+                self,
+                QL500 | QL550 | QL560 | QL570 | QL580N | QL650TD | QL1050 | QL1060N
+            ),
+            network: matches!(
+                self,
+                QL580N | QL710W | QL720NW | QL810W | QL820NWB | QL1060N | QL1110NWB | QL1115NWB
+            ),
+            pins: self.pins(),
+        }
+    }
+}
+
+/// Features of a printer model, see `Model::capabilities`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    /// PackBits compressed raster lines
+    pub compression: bool,
+    /// Red and black printing on DK-22251 tape
+    pub two_colors: bool,
+    /// 300 x 600 dpi printing
+    pub high_resolution: bool,
+    /// Automatic cutter
+    pub cutter: bool,
+    /// ESC i ! automatic status notification
+    pub auto_status: bool,
+    /// Wired or wireless network interface
+    pub network: bool,
+    /// Maximum raster width in dots
+    pub pins: u32,
+}
+
+impl Capabilities {
+    /// Bytes per raster line.
+    pub fn line_bytes(&self) -> usize {
+        (self.pins / 8) as usize
+    }
+
+    /// Whether `media` fits the print head and can be printed in its colors.
+    pub fn supports_media(&self, media: Media) -> bool {
+        if media == Media::Continuous(ContinuousType::Continuous62Red) && !self.two_colors {
+            return false;
+        }
+        media.spec().required_pins() <= self.pins
+    }
+
+    /// Every media the model can print on.
+    pub fn supported_media(&self) -> Vec<Media> {
+        Media::ALL
+            .iter()
+            .copied()
+            .filter(|media| self.supports_media(*media))
+            .collect()
+    }
+}
+
+/// Model reported by the printer in its status reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReportedModel {
+    Known(Model),
+    /// Model code not listed in `Model`
+    Unknown(u8),
+}
+
+impl ReportedModel {
+    pub fn from_code(code: u8) -> Self {
+        match Model::from_code(code) {
+            Some(model) => Self::Known(model),
+            None => Self::Unknown(code),
+        }
+    }
+
+    /// The model, `None` when the code is unknown.
+    pub fn model(&self) -> Option<Model> {
+        match self {
+            Self::Known(model) => Some(*model),
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ReportedModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Known(model) => model.fmt(f),
+            Self::Unknown(code) => write!(f, "unknown model {:#04x}", code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_model_lookups() {
+        for model in Model::ALL {
+            assert_eq!(Model::from_pid(model.pid()), Some(model));
+            assert_eq!(model.name().parse::<Model>(), Ok(model));
+            if model != Model::QL500 {
+                assert_eq!(Model::from_code(model.code()), Some(model));
+            }
+        }
+        assert_eq!(Model::from_code(0x4F), Some(Model::QL550));
+        assert_eq!(Model::from_code(0xFF), None);
+        assert_eq!(Model::from_pid(0x0000), None);
+
+        assert_eq!("ql1110nwb".parse::<Model>(), Ok(Model::QL1110NWB));
+        assert_eq!("QL-820NW".parse::<Model>(), Ok(Model::QL820NWB));
+        assert_eq!("QL-9000".parse::<Model>(), Err(InvalidPrinterName));
+
+        assert_eq!(ReportedModel::from_code(0x35), ReportedModel::Known(Model::QL700));
+        assert_eq!(ReportedModel::from_code(0x99).to_string(), "unknown model 0x99");
+    }
+
+    #[test]
+    fn test_capabilities() {
+        let wide = Media::Continuous(ContinuousType::Continuous102);
+        let red = Media::Continuous(ContinuousType::Continuous62Red);
+
+        let ql1110 = Model::QL1110NWB.capabilities();
+        assert_eq!(ql1110.line_bytes(), 162);
+        assert!(ql1110.supports_media(wide));
+        assert!(!ql1110.supports_media(red));
+        assert_eq!(ql1110.supported_media().len(), Media::ALL.len() - 1);
+
+        let ql820 = Model::QL820NWB.capabilities();
+        assert_eq!(ql820.line_bytes(), 90);
+        assert!(!ql820.supports_media(wide));
+        assert!(ql820.supports_media(red));
+        assert!(ql820.compression && ql820.network);
+    }
 }
@@ -0,0 +1,324 @@
+//! Several printers used as one.
+//!
+//! `PrinterPool` routes every job to an idle printer configured for the
+//! requested media, after checking the media is actually installed. When a
+//! printer fails the remaining pages are printed on another one.
+
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{
+    error::Error,
+    media::Media,
+    model::Model,
+    printer::Printer,
+    transport::{Transport, UsbTransport},
+    utils::TwoColorMatrix,
+    Matrix,
+};
+
+/// Usage of one printer of a pool, see `PrinterPool::usage`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PrinterUsage {
+    pub serial: String,
+    pub model: Model,
+    pub media: Media,
+    /// Jobs completed on this printer
+    pub jobs: usize,
+    /// Pages printed on this printer
+    pub pages: usize,
+    /// Jobs which failed on this printer and were moved to another one
+    pub failures: usize,
+    /// Time spent printing
+    pub busy: Duration,
+    /// Share of the pool's lifetime spent printing, from 0.0 to 1.0
+    pub utilization: f64,
+}
+
+struct Member<T: Transport> {
+    printer: Mutex<Printer<T>>,
+    serial: String,
+    model: Model,
+    media: Media,
+    usage: Mutex<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    jobs: usize,
+    pages: usize,
+    failures: usize,
+    busy: Duration,
+}
+
+/// Printers sharing the print jobs.
+///
+/// Each printer prints one job at a time, a job is given to an idle printer
+/// whose `Config` media is the requested one and whose status reports that
+/// media installed, the same check as `Status::check_media`. Jobs wait while
+/// every matching printer is busy.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{Config, ContinuousType, DieCutType, Media, Model, Printer, PrinterPool, SimulatedPrinter};
+/// let continuous = Media::Continuous(ContinuousType::Continuous62);
+/// let die_cut = Media::DieCut(DieCutType::DieCut29x90);
+/// let printers = vec![("A", continuous), ("B", die_cut)].into_iter().map(|(serial, media)| {
+///     let device = SimulatedPrinter::new(Model::QL820NWB, Some(media));
+///     let config = Config::new(Model::QL820NWB, serial.to_string(), media);
+///     Printer::with_transport(device, config)
+/// });
+/// let pool = PrinterPool::new(printers);
+///
+/// let serial = pool.print(die_cut, vec![vec![vec![0xFF; 90]; 10]])?;
+/// assert_eq!(serial, "B");
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+pub struct PrinterPool<T: Transport = UsbTransport> {
+    members: Vec<Member<T>>,
+    // 印刷中のプリンター、空きが出るとreleasedで通知する
+    busy: Mutex<Vec<bool>>,
+    released: Condvar,
+    created: Instant,
+}
+
+impl<T: Transport> PrinterPool<T> {
+    /// Pool the printers, each keeps the media of its `Config`.
+    ///
+    /// The order of `printers` is the order in which idle printers are
+    /// tried and in which `usage` lists them.
+    pub fn new(printers: impl IntoIterator<Item = Printer<T>>) -> Self {
+        let members: Vec<Member<T>> = printers
+            .into_iter()
+            .map(|printer| {
+                let config = printer.config();
+                Member {
+                    serial: config.serial.clone(),
+                    model: config.model,
+                    media: config.media,
+                    printer: Mutex::new(printer),
+                    usage: Mutex::new(Counters::default()),
+                }
+            })
+            .collect();
+
+        PrinterPool {
+            busy: Mutex::new(vec![false; members.len()]),
+            members,
+            released: Condvar::new(),
+            created: Instant::now(),
+        }
+    }
+
+    /// Print single-color labels on a printer loaded with `media`.
+    ///
+    /// # Returns
+    /// * `Ok(String)` - Serial number of the printer which printed the last page
+    /// * `Err(Error::NoPrinterAvailable)` - No printer has `media`, or every one of them failed
+    /// * `Err(Error)` - Any other error of `Printer::print`
+    pub fn print(&self, media: Media, images: Vec<Matrix>) -> Result<String, Error> {
+        self.dispatch(media, images, |printer, images| printer.print(images.iter().cloned()))
+    }
+
+    /// Print two-color labels on a printer loaded with `media`, see `print`.
+    pub fn print_two_color(&self, media: Media, images: Vec<TwoColorMatrix>) -> Result<String, Error> {
+        self.dispatch(media, images, |printer, images| {
+            printer.print_two_color(images.iter().cloned())
+        })
+    }
+
+    /// Usage of every printer, in the order they were added.
+    pub fn usage(&self) -> Vec<PrinterUsage> {
+        let lifetime = self.created.elapsed().as_secs_f64();
+        self.members
+            .iter()
+            .map(|member| {
+                let usage = member.usage.lock().unwrap();
+                PrinterUsage {
+                    serial: member.serial.clone(),
+                    model: member.model,
+                    media: member.media,
+                    jobs: usage.jobs,
+                    pages: usage.pages,
+                    failures: usage.failures,
+                    busy: usage.busy,
+                    utilization: if lifetime > 0.0 {
+                        (usage.busy.as_secs_f64() / lifetime).min(1.0)
+                    } else {
+                        0.0
+                    },
+                }
+            })
+            .collect()
+    }
+
+    fn dispatch<P: Clone>(
+        &self,
+        media: Media,
+        mut images: Vec<P>,
+        print: impl Fn(&Printer<T>, &[P]) -> Result<(), Error>,
+    ) -> Result<String, Error> {
+        // 失敗した、またはメディアが違ったプリンター
+        let mut excluded: HashSet<usize> = HashSet::new();
+
+        loop {
+            let candidates: Vec<usize> = (0..self.members.len())
+                .filter(|index| !excluded.contains(index) && self.members[*index].media == media)
+                .collect();
+            if candidates.is_empty() {
+                return Err(Error::NoPrinterAvailable(media));
+            }
+
+            let index = self.acquire(&candidates);
+            let member = &self.members[index];
+            let printer = member.printer.lock().unwrap();
+
+            if let Err(err) = printer.check_status().and_then(|status| status.check_media(media)) {
+                warn!("Skipping printer {}: {}", member.serial, err);
+                excluded.insert(index);
+                drop(printer);
+                self.release(index);
+                continue;
+            }
+
+            debug!("Printing {} pages on {}", images.len(), member.serial);
+            let started = Instant::now();
+            // ページは送信するときに1枚ずつ複製し、失敗時は残りを次のプリンターへ回す
+            let result = print(&printer, &images);
+            drop(printer);
+
+            let mut usage = member.usage.lock().unwrap();
+            usage.busy += started.elapsed();
+            let outcome = match result {
+                Ok(()) => {
+                    usage.jobs += 1;
+                    usage.pages += images.len();
+                    Ok(member.serial.clone())
+                }
+                Err(err) => {
+                    let (printed, source) = match &err {
+                        Error::JobInterrupted { printed, source } => (printed.len(), &**source),
+                        err => (0, err),
+                    };
+                    usage.pages += printed;
+                    if can_fail_over(source) {
+                        usage.failures += 1;
+                        info!(
+                            "Printer {} failed after {} pages ({}), moving the job",
+                            member.serial, printed, source
+                        );
+                        excluded.insert(index);
+                        images.drain(..printed);
+                        Err(None)
+                    } else {
+                        Err(Some(err))
+                    }
+                }
+            };
+            drop(usage);
+            self.release(index);
+
+            match outcome {
+                Ok(serial) => return Ok(serial),
+                Err(Some(err)) => return Err(err),
+                Err(None) => continue,
+            }
+        }
+    }
+
+    /// Mark the first idle printer of `candidates` busy, waiting until one is released.
+    fn acquire(&self, candidates: &[usize]) -> usize {
+        let mut busy = self.busy.lock().unwrap();
+        loop {
+            if let Some(&index) = candidates.iter().find(|&&index| !busy[index]) {
+                busy[index] = true;
+                return index;
+            }
+            busy = self.released.wait(busy).unwrap();
+        }
+    }
+
+    fn release(&self, index: usize) {
+        self.busy.lock().unwrap()[index] = false;
+        self.released.notify_all();
+    }
+}
+
+fn can_fail_over(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::PrinterError(_)
+            | Error::DeviceOffline
+            | Error::MediaMismatch { .. }
+            | Error::NoMediaInstalled
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, ContinuousType, PrinterErrors, SimulatedPrinter};
+
+    fn pool(serials: &[&str], media: Media) -> (PrinterPool<SimulatedPrinter>, Vec<SimulatedPrinter>) {
+        let devices: Vec<SimulatedPrinter> = serials
+            .iter()
+            .map(|_| SimulatedPrinter::new(Model::QL820NWB, Some(media)))
+            .collect();
+        let printers = serials.iter().zip(&devices).map(|(serial, device)| {
+            let config = Config::new(Model::QL820NWB, serial.to_string(), media);
+            Printer::with_transport(device.clone(), config)
+        });
+        (PrinterPool::new(printers), devices)
+    }
+
+    fn page(value: u8) -> Matrix {
+        vec![vec![value; 90]; 4]
+    }
+
+    #[test]
+    fn test_routes_by_installed_media() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (pool, devices) = pool(&["A", "B"], media);
+        devices[0].set_media(Some(Media::Continuous(ContinuousType::Continuous29)));
+
+        assert_eq!(pool.print(media, vec![page(1)]).unwrap(), "B");
+        assert!(devices[0].pages().is_empty());
+
+        let other = Media::Continuous(ContinuousType::Continuous29);
+        assert!(matches!(pool.print(other, vec![page(1)]), Err(Error::NoPrinterAvailable(_))));
+    }
+
+    #[test]
+    fn test_fail_over_on_printer_error() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (pool, devices) = pool(&["A", "B"], media);
+        devices[0].raise_errors(PrinterErrors::END_OF_MEDIA);
+
+        let pages = vec![page(1), page(2)];
+        assert_eq!(pool.print(media, pages.clone()).unwrap(), "B");
+        assert_eq!(devices[1].pages(), pages);
+
+        let usage = pool.usage();
+        assert_eq!((usage[0].jobs, usage[0].failures), (0, 1));
+        assert_eq!((usage[1].jobs, usage[1].pages), (1, 2));
+        assert!(usage[1].busy > Duration::ZERO && usage[1].utilization > 0.0);
+    }
+
+    #[test]
+    fn test_concurrent_jobs_use_idle_printers() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (pool, devices) = pool(&["A", "B"], media);
+
+        std::thread::scope(|scope| {
+            for n in 0..4 {
+                let pool = &pool;
+                scope.spawn(move || pool.print(media, vec![page(n)]).unwrap());
+            }
+        });
+
+        assert_eq!(devices[0].pages().len() + devices[1].pages().len(), 4);
+        assert_eq!(pool.usage().iter().map(|usage| usage.jobs).sum::<usize>(), 4);
+    }
+}
@@ -1,35 +1,40 @@
 use log::{debug, error, info, warn};
-use rusb::{Context, Device, DeviceDescriptor, DeviceHandle, Direction, TransferType, UsbContext};
-use std::time::Duration;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
 
 use crate::{
-    error::{Error, PrinterError},
+    bitmap::Bitmap,
+    cancel::CancelToken,
+    encoder::{self, RasterEncoder},
+    error::{Error, PrinterErrors},
+    event::{JobEvent, JobObserver, JobReport},
+    hotplug::HotplugWatcher,
     media::Media,
     model::Model,
+    rows::{RowSource, Rows},
+    status::{Notification, Phase, Status, StatusType},
+    transport::{DeviceInfo, TcpTransport, Transport, UsbTransport, STATUS_SIZE},
     utils::TwoColorMatrix,
     Matrix,
 };
 
-// Vendoer id of Brother Industries, Ltd
-const VENDOR_ID: u16 = 0x04f9;
+// 印刷完了待ちで受け取るステータスの上限の既定値（約5秒のタイムアウト）
+const MAX_ATTEMPTS: u32 = 100;
 
-#[derive(Debug, Clone, Copy)]
-#[allow(dead_code)]
-struct Endpoint {
-    config: u8,
-    iface: u8,
-    setting: u8,
-    address: u8,
-}
+// print_pipelinedで送っておくページ数、印刷中のページとその次のページ
+const PIPELINE_DEPTH: usize = 2;
 
-pub struct Printer {
-    handle: Box<DeviceHandle<Context>>,
-    endpoint_out: Endpoint,
-    endpoint_in: Endpoint,
+/// Brother QL printer driving the raster protocol over a `Transport`.
+///
+/// The transport defaults to `UsbTransport`, which is what `Printer::new` opens.
+pub struct Printer<T: Transport = UsbTransport> {
+    transport: T,
     config: Config,
+    observer: Option<Box<dyn JobObserver>>,
+    cancel: CancelToken,
 }
 
-impl Printer {
+impl Printer<UsbTransport> {
     /// Create a new printer instance with the specified configuration.
     ///
     /// This constructor handles USB device enumeration, connection, and initialization.
@@ -45,78 +50,176 @@ impl Printer {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType, Printer};
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer};
     /// let config = Config::new(Model::QL820NWB, "E8N117P02180".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62));
     /// let printer = Printer::new(config)?;
-    /// # Ok::<(), ptouch::Error>(())
+    /// # Ok::<(), ql_label::Error>(())
     /// ```
     pub fn new(config: Config) -> Result<Self, Error> {
-        // rusb::set_log_level(rusb::LogLevel::Debug);
-        match Context::new() {
-            Ok(mut context) => {
-                match Self::open_device(&mut context, config.model.pid(), config.serial.clone()) {
-                    Ok((mut device, device_desc, handle)) => {
-                        handle.reset()?;
-
-                        let endpoint_in = match Self::find_endpoint(
-                            &mut device,
-                            &device_desc,
-                            Direction::In,
-                            TransferType::Bulk,
-                        ) {
-                            Some(endpoint) => endpoint,
-                            None => return Err(Error::MissingEndpoint),
-                        };
-
-                        let endpoint_out = match Self::find_endpoint(
-                            &mut device,
-                            &device_desc,
-                            Direction::Out,
-                            TransferType::Bulk,
-                        ) {
-                            Some(endpoint) => endpoint,
-                            None => return Err(Error::MissingEndpoint),
-                        };
-
-                        // QL-800では`has_kernel_driver`が`true`となる
-                        // QL-820NWBでは`has_kernel_driver`が`false`となる
-                        // `has_kernel_driver`が`true`の場合に、カーネルドライバーをデタッチしないとエラーとなる
-                        //
-                        handle.set_auto_detach_kernel_driver(true)?;
-                        let has_kernel_driver = match handle.kernel_driver_active(0) {
-                            Ok(true) => {
-                                handle.detach_kernel_driver(0).ok();
-                                true
-                            }
-                            _ => false,
-                        };
-                        info!(" Kernel driver support is {}", has_kernel_driver);
-                        handle.set_active_configuration(1)?;
-                        handle.claim_interface(0)?;
-                        handle.set_alternate_setting(0, 0)?;
-
-                        Ok(Printer {
-                            handle: Box::new(handle),
-                            endpoint_out,
-                            endpoint_in,
-                            config,
-                        })
-                    }
-                    Err(err) => {
-                        debug!("Device connection failed: {:?}", err);
-                        Err(Error::DeviceOffline)
-                    }
-                }
-            }
-            Err(err) => Err(Error::UsbError(err)),
+        config.validate()?;
+        let transport = UsbTransport::open(config.model.pid(), &config.serial)?;
+        Ok(Self::with_transport(transport, config))
+    }
+
+    /// List the Brother label printers attached to USB.
+    ///
+    /// This is what `lsusb -v` would show, restricted to known models, so the
+    /// serial number does not need to be looked up by hand.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{ContinuousType, Media, Printer};
+    /// for device in Printer::discover()? {
+    ///     println!("{:?} {}", device.model, device.serial);
+    ///     let config = device.config(Media::Continuous(ContinuousType::Continuous62));
+    ///     let printer = Printer::new(config)?;
+    /// }
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn discover() -> Result<Vec<DeviceInfo>, Error> {
+        UsbTransport::discover()
+    }
+
+    /// Open the USB device again, e.g. after it was unplugged or power-cycled.
+    ///
+    /// The device is looked up with the model and serial number of the
+    /// configuration, exactly like `Printer::new`.
+    pub fn reconnect(&mut self) -> Result<(), Error> {
+        self.transport = UsbTransport::open(self.config.model.pid(), &self.config.serial)?;
+        Ok(())
+    }
+
+    /// Wait until this printer is attached again, then reopen it.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType, HotplugWatcher, Printer};
+    /// # use std::time::Duration;
+    /// # let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    /// #                         Media::Continuous(ContinuousType::Continuous62));
+    /// let watcher = HotplugWatcher::start()?;
+    /// let mut printer = Printer::new(config)?;
+    /// if printer.check_status().is_err() {
+    ///     printer.reconnect_when_attached(&watcher, Duration::from_secs(60))?;
+    /// }
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn reconnect_when_attached(
+        &mut self,
+        watcher: &HotplugWatcher,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        watcher.wait_for(&self.config.serial, timeout)?;
+        self.reconnect()
+    }
+}
+
+impl Printer<TcpTransport> {
+    /// Connect to a network printer through its raw TCP port.
+    ///
+    /// The same raster stream as for USB is sent over the socket, so every
+    /// print and status method works unchanged.
+    ///
+    /// # Arguments
+    /// * `addr` - Address of the printer, usually on port 9100
+    /// * `config` - Printer configuration, the serial number is not used for lookup
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer};
+    /// let config = Config::new(Model::QL820NWB, "E8N117P02180".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous62));
+    /// let printer = Printer::connect_tcp("192.168.1.20:9100", config)?;
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn connect_tcp(addr: impl std::net::ToSocketAddrs, config: Config) -> Result<Self, Error> {
+        config.validate()?;
+        let transport = TcpTransport::connect(addr)?;
+        Ok(Self::with_transport(transport, config))
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Printer<crate::transport::UsblpTransport> {
+    /// Open a printer through the Linux `usblp` character device.
+    ///
+    /// Unlike `Printer::new` this keeps the kernel driver attached and does not
+    /// need libusb, only read and write access to the device node.
+    ///
+    /// # Arguments
+    /// * `path` - Device node such as `/dev/usb/lp0`
+    /// * `config` - Printer configuration, the serial number is not used for lookup
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer};
+    /// let config = Config::new(Model::QL800, "000G0Z000000".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous29));
+    /// let printer = Printer::open_usblp("/dev/usb/lp0", config)?;
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn open_usblp(path: impl AsRef<std::path::Path>, config: Config) -> Result<Self, Error> {
+        config.validate()?;
+        let transport = crate::transport::UsblpTransport::open(path)?;
+        Ok(Self::with_transport(transport, config))
+    }
+}
+
+impl<T: Transport> Printer<T> {
+    /// Create a printer instance on top of an already opened transport.
+    ///
+    /// Use this to drive the printer through something other than libusb,
+    /// for example a network socket or a test double.
+    ///
+    /// # Arguments
+    /// * `transport` - Opened connection to the printer
+    /// * `config` - Printer configuration containing model, serial, media, and print settings
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer, UsbTransport};
+    /// let config = Config::new(Model::QL800, "000G0Z000000".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous29));
+    /// let transport = UsbTransport::open(Model::QL800.pid(), "000G0Z000000")?;
+    /// let printer = Printer::with_transport(transport, config);
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn with_transport(transport: T, config: Config) -> Self {
+        Printer {
+            transport,
+            config,
+            observer: None,
+            cancel: CancelToken::new(),
         }
     }
 
+    /// Configuration the printer was opened with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Token to cancel the running print job from another thread.
+    ///
+    /// A token triggered while the printer is idle cancels the next job,
+    /// such as one queued in a `SharedPrinter`. The request is cleared when
+    /// that job returns.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    /// Report the progress of every print job to `observer`.
+    ///
+    /// A previously set observer is replaced. See `JobObserver` for an example.
+    pub fn set_observer(&mut self, observer: impl JobObserver + 'static) {
+        self.observer = Some(Box::new(observer));
+    }
+
     /// Cancel current print job and reset printer state.
     ///
     /// Sends an initialization command to cancel any ongoing print job
-    /// and reset the printer to a ready state.
+    /// and reset the printer to a ready state. To stop a job `print` is
+    /// still running, use `cancel_token` instead.
     ///
     /// # Returns
     /// * `Ok(())` - Cancel command sent successfully
@@ -124,15 +227,15 @@ impl Printer {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType, Printer};
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer};
     /// # let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     /// #                         Media::Continuous(ContinuousType::Continuous62));
     /// let printer = Printer::new(config)?;
     /// printer.cancel()?; // Cancel any ongoing job
-    /// # Ok::<(), ptouch::Error>(())
+    /// # Ok::<(), ql_label::Error>(())
     /// ```
     pub fn cancel(&self) -> Result<(), Error> {
-        let buf = self.initialize();
+        let buf = encoder::initialize();
         self.write(buf)?;
         Ok(())
     }
@@ -148,7 +251,7 @@ impl Printer {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType, Printer};
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer};
     /// # let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     /// #                         Media::Continuous(ContinuousType::Continuous62));
     /// let printer = Printer::new(config)?;
@@ -156,11 +259,12 @@ impl Printer {
     ///     Ok(status) => println!("Printer ready: {:?}", status),
     ///     Err(e) => eprintln!("Printer error: {:?}", e),
     /// }
-    /// # Ok::<(), ptouch::Error>(())
+    /// # Ok::<(), ql_label::Error>(())
     /// ```
     pub fn check_status(&self) -> Result<Status, Error> {
         self.request_status()?;
-        self.read_status()
+        // ジョブ外の問い合わせなので、キャンセル済みのトークンに影響されない
+        self.poll_status(Duration::from_millis(1000), None)
     }
 
     /// Print single-color labels.
@@ -173,6 +277,8 @@ impl Printer {
     ///
     /// # Returns
     /// * `Ok(())` - Print job completed successfully
+    /// * `Err(Error::Cancelled)` - The job was cancelled through `cancel_token`
+    /// * `Err(Error::JobInterrupted)` - The job stopped part way, with the pages printed so far
     /// * `Err(Error)` - Printer error, communication error, or media mismatch
     ///
     /// # Image Format
@@ -182,7 +288,7 @@ impl Printer {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType, Printer, Matrix};
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer, Matrix};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62));
     /// let printer = Printer::new(config)?;
@@ -191,9 +297,12 @@ impl Printer {
     /// let image_data: Matrix = vec![vec![0xFF; 90]; 300]; // 300 lines of solid black
     /// 
     /// printer.print(vec![image_data].into_iter())?;
-    /// # Ok::<(), ptouch::Error>(())
+    /// # Ok::<(), ql_label::Error>(())
     /// ```
     pub fn print(&self, images: impl Iterator<Item = Matrix>) -> Result<(), Error> {
+        let _job = self.cancel.job();
+        self.config.validate()?;
+
         info!("Requesting printer status before print job");
 
         self.request_status()?;
@@ -204,7 +313,7 @@ impl Printer {
                 status.check_media(self.config.media)?;
 
                 info!("Starting print job");
-                self.print_label(images)?;
+                self.print_label(images.map(|image| Ok(Rows::from(image))), |page| Some(page.clone()))?;
                 Ok(())
             }
             Err(err) => {
@@ -214,6 +323,167 @@ impl Printer {
         }
     }
 
+    /// Print single-color labels without waiting a full round trip per page.
+    ///
+    /// The next page is encoded, and compressed if enabled, on a worker thread
+    /// and sent while the current one prints, so the printer always has the
+    /// following page buffered. Every page is counted as printed from its
+    /// completion notification, or from the printer returning to the
+    /// receiving phase when it sends none.
+    ///
+    /// Models without automatic status notification are printed page by page
+    /// like `print`.
+    ///
+    /// # Arguments
+    /// * `images` - Iterator of `Matrix` (`Vec<Vec<u8>>`) containing 1-bit bitmap data
+    ///
+    /// # Returns
+    /// * `Ok(JobReport)` - Pages printed, elapsed time and labels per minute
+    /// * `Err(Error::InvalidConfig)` - A `RecoveryPolicy` is set, buffered pages cannot be resent
+    /// * `Err(Error)` - Same errors as `print`
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, DieCutType, Printer, Matrix};
+    /// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    ///                         Media::DieCut(DieCutType::DieCut29x90));
+    /// let printer = Printer::new(config)?;
+    ///
+    /// let labels: Vec<Matrix> = vec![vec![vec![0xFF; 90]; 991]; 100];
+    /// let report = printer.print_pipelined(labels.into_iter())?;
+    /// println!("{:.1} labels per minute", report.labels_per_minute);
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn print_pipelined(
+        &self,
+        images: impl Iterator<Item = Matrix> + Send,
+    ) -> Result<JobReport, Error> {
+        let _job = self.cancel.job();
+        if self.config.recovery.is_some() {
+            return Err(Error::InvalidConfig(
+                "Pipelined printing cannot recover from printer errors, remove the RecoveryPolicy"
+                    .to_string(),
+            ));
+        }
+        if !self.config.model.capabilities().auto_status {
+            debug!("Pipelined printing not available, printing page by page");
+            let started = Instant::now();
+            let mut pages = 0;
+            self.print(images.inspect(|_| pages += 1))?;
+            return Ok(JobReport::new(pages, started.elapsed()));
+        }
+
+        self.config.validate()?;
+
+        info!("Requesting printer status before pipelined print job");
+        self.request_status()?;
+        let status = self.read_status()?;
+        info!("Verifying correct media is installed");
+        status.check_media(self.config.media)?;
+
+        let encoder = RasterEncoder::new(self.config.clone())?;
+        let preamble = encoder.preamble()?;
+
+        let (lower, upper) = images.size_hint();
+        let pages = if upper == Some(lower) { Some(lower) } else { None };
+        self.emit(JobEvent::Started { pages });
+        let started = Instant::now();
+
+        let printed = std::thread::scope(|scope| {
+            // 送信待ちの次の1ページを先にエンコードしておく
+            let (sender, receiver) = std::sync::mpsc::sync_channel::<(usize, Result<Vec<u8>, Error>)>(1);
+            let encoder = &encoder;
+            scope.spawn(move || {
+                let mut iter = images.enumerate().peekable();
+                while let Some((index, image)) = iter.next() {
+                    let last = iter.peek().is_none();
+                    let mut buf = Vec::new();
+                    let page = encoder.page(&mut buf, image, index == 0, last).map(|()| buf);
+                    let failed = page.is_err();
+                    if sender.send((index, page)).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+
+            let mut printed: Vec<usize> = Vec::new();
+            // 送信済みで、まだ印刷の完了していないページ
+            let mut in_flight: VecDeque<usize> = VecDeque::new();
+            let mut tracker = CompletionTracker::default();
+            let mut pages = receiver.iter();
+            let mut sending = true;
+
+            loop {
+                // 印刷中のページの次のページまで送っておく
+                while sending && in_flight.len() < PIPELINE_DEPTH {
+                    let (index, page) = match pages.next() {
+                        Some(page) => page,
+                        None => {
+                            sending = false;
+                            break;
+                        }
+                    };
+                    if self.cancel.is_cancelled() {
+                        return Err(self.cancelled(printed.len()));
+                    }
+                    let buf = match page {
+                        Ok(buf) => buf,
+                        Err(err) => return Err(self.interrupted(index, printed, err)),
+                    };
+                    let buf = if index == 0 {
+                        [preamble.as_slice(), buf.as_slice()].concat()
+                    } else {
+                        buf
+                    };
+                    if let Err(err) = self.write(buf) {
+                        return Err(self.interrupted(index, printed, err));
+                    }
+                    self.emit(JobEvent::PageSent { page: index });
+                    in_flight.push_back(index);
+                }
+
+                let page = match in_flight.front() {
+                    Some(&page) => page,
+                    None => break,
+                };
+                match self.wait_for_completion(page, &mut tracker) {
+                    Ok(()) => {
+                        in_flight.pop_front();
+                        printed.push(page);
+                    }
+                    Err(Error::Cancelled { .. }) => return Err(self.cancelled(printed.len())),
+                    Err(err) => {
+                        // 受信済みの後続ページを印刷させない
+                        if in_flight.len() > 1 {
+                            if let Err(err) = self.invalidate() {
+                                error!("Failed to discard buffered pages: {}", err);
+                            }
+                        }
+                        return Err(self.interrupted(page, printed, err));
+                    }
+                }
+            }
+
+            if let Some(&page) = printed.last() {
+                if let Err(err) = self.invalidate() {
+                    return Err(self.interrupted(page, printed, err));
+                }
+            }
+            Ok(printed.len())
+        })?;
+
+        let report = JobReport::new(printed, started.elapsed());
+        info!(
+            "Pipelined print job completed: {} pages, {:.1} labels per minute",
+            report.pages, report.labels_per_minute
+        );
+        self.emit(JobEvent::Completed {
+            pages: report.pages,
+            elapsed: report.elapsed,
+        });
+        Ok(report)
+    }
+
     /// Print two-color labels using black and red colors.
     ///
     /// This method is specifically designed for QL-820NWB printers with
@@ -225,11 +495,13 @@ impl Printer {
     ///
     /// # Returns
     /// * `Ok(())` - Print job completed successfully
+    /// * `Err(Error::Cancelled)` - The job was cancelled through `cancel_token`
+    /// * `Err(Error::JobInterrupted)` - The job stopped part way, with the pages printed so far
     /// * `Err(Error)` - Printer error, communication error, or invalid configuration
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType, Printer, TwoColorMatrix};
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer, TwoColorMatrix};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62Red))
     ///     .two_colors(true);
@@ -244,9 +516,11 @@ impl Printer {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn print_two_color(&self, images: impl Iterator<Item = TwoColorMatrix>) -> Result<(), Error> {
+        let _job = self.cancel.job();
         if !self.config.two_colors {
             return Err(Error::InvalidConfig("Two-color printing not enabled in config".to_string()));
         }
+        self.config.validate()?;
 
         info!("Requesting printer status before two-color print job");
 
@@ -258,8 +532,9 @@ impl Printer {
                 status.check_media(self.config.media)?;
 
                 info!("Starting two-color print job");
-                let alternating_images = images.map(|two_color| two_color.to_alternating_matrix());
-                self.print_label(alternating_images)?;
+                let alternating_images =
+                    images.map(|two_color| Ok(Rows::from(two_color.to_alternating_matrix())));
+                self.print_label(alternating_images, |page| Some(page.clone()))?;
                 Ok(())
             }
             Err(err) => {
@@ -269,206 +544,204 @@ impl Printer {
         }
     }
 
-    // Private helper methods
-
-    fn open_device(
-        context: &mut Context,
-        pid: u16,
-        serial: String,
-    ) -> Result<(Device<Context>, DeviceDescriptor, DeviceHandle<Context>), Error> {
-        let devices = context.devices()?;
+    /// Print single-color labels drawn on `Bitmap`s.
+    ///
+    /// Every bitmap must be as wide as the print head and its resolution
+    /// must be 300 x 300 dpi, or 300 x 600 dpi with `Config::high_resolution`.
+    /// Bitmaps are checked as they are taken from `images` and streamed like
+    /// `print_rows`, so the pages before an invalid one are printed.
+    ///
+    /// # Arguments
+    /// * `images` - Iterator of `Bitmap`, one per page
+    ///
+    /// # Returns
+    /// * `Ok(())` - Print job completed successfully
+    /// * `Err(Error::JobInterrupted)` - A bitmap does not fit the printer or the configured
+    ///   resolution, with `Error::InvalidBitmap` as source and the pages printed before it
+    /// * `Err(Error)` - Same errors as `print`
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Bitmap, Config, Model, Media, ContinuousType, Printer};
+    /// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous62));
+    /// let printer = Printer::new(config)?;
+    ///
+    /// let mut label = Bitmap::for_model(Model::QL820NWB, 300);
+    /// label.set(100, 100, true);
+    /// printer.print_bitmaps(vec![label].into_iter())?;
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn print_bitmaps(&self, images: impl Iterator<Item = Bitmap>) -> Result<(), Error> {
+        let _job = self.cancel.job();
+        self.config.validate()?;
 
-        if devices.is_empty() {
-            warn!("Unable to enumerate USB devices");
-            return Err(Error::DeviceListNotReadable);
-        }
-        for device in devices.iter() {
-            let device_desc = match device.device_descriptor() {
-                Ok(d) => d,
-                Err(err) => {
-                    debug!("{:#?}", err);
-                    continue;
-                }
-            };
-            debug!(
-                "vender_id: {:x},  product_id: {:x}",
-                device_desc.vendor_id(),
-                device_desc.product_id()
-            );
-            if device_desc.vendor_id() == VENDOR_ID && device_desc.product_id() == pid {
-                match device.open() {
-                    Ok(handle) => {
-                        let timeout = Duration::from_secs(1);
-                        let languages = handle.read_languages(timeout)?;
-
-                        if languages.len() > 0 {
-                            let language = languages[0];
-                            match handle.read_serial_number_string(language, &device_desc, timeout)
-                            {
-                                Ok(s) => {
-                                    if s == serial {
-                                        info!("Connected to printer (serial: {})", serial);
-                                        return Ok((device, device_desc, handle));
-                                    } else {
-                                        continue;
-                                    }
-                                }
-                                Err(err) => {
-                                    debug!("Cannot read device serial number: {:?}", err);
-                                    continue;
-                                }
-                            }
-                        } else {
-                            continue;
-                        }
-                    }
-                    Err(err) => {
-                        debug!("Unable to open USB device: {:?}", err);
-                        continue;
-                    }
-                }
+        info!("Requesting printer status before bitmap print job");
+        self.request_status()?;
+        let status = self.read_status()?;
+        info!("Verifying correct media is installed");
+        status.check_media(self.config.media)?;
+
+        let model = self.config.model;
+        let pins = model.pins();
+        let dpi = (300, if self.config.high_resolution { 600 } else { 300 });
+        // 取り出したときに検査し、Matrixに写さず行ごとに送る
+        let pages = images.enumerate().map(move |(page, image)| {
+            if image.width() != pins {
+                return Err(Error::InvalidBitmap(format!(
+                    "page {} is {} pixels wide, {} prints {} pixels",
+                    page,
+                    image.width(),
+                    model,
+                    pins
+                )));
             }
-        }
-        error!("No printer found with serial number: {}", serial);
-        Err(Error::DeviceOffline)
-    }
-
-    fn find_endpoint(
-        device: &mut Device<Context>,
-        device_desc: &DeviceDescriptor,
-        direction: Direction,
-        transfer_type: TransferType,
-    ) -> Option<Endpoint> {
-        for n in 0..device_desc.num_configurations() {
-            let config_desc = match device.config_descriptor(n) {
-                Ok(c) => c,
-                Err(_) => continue,
-            };
-            for interface in config_desc.interfaces() {
-                for interface_desc in interface.descriptors() {
-                    for endpoint_desc in interface_desc.endpoint_descriptors() {
-                        if endpoint_desc.direction() == direction
-                            && endpoint_desc.transfer_type() == transfer_type
-                        {
-                            return Some(Endpoint {
-                                config: config_desc.number(),
-                                iface: interface_desc.interface_number(),
-                                setting: interface_desc.setting_number(),
-                                address: endpoint_desc.address(),
-                            });
-                        }
-                    }
-                }
+            if image.dpi() != dpi {
+                return Err(Error::InvalidBitmap(format!(
+                    "page {} is {:?} dpi, the printer is configured for {:?} dpi",
+                    page,
+                    image.dpi(),
+                    dpi
+                )));
             }
-        }
-        None
+            Ok(image.into_rows())
+        });
+
+        info!("Starting bitmap print job");
+        self.print_label(pages, |page| Some(page.clone()))
     }
 
+    /// Print single-color labels whose raster lines are produced on demand.
+    ///
+    /// Each page is a `RowSource` announcing its raster count. Lines are
+    /// pulled while the page is encoded and sent in chunks of about 16 KiB,
+    /// so memory use stays flat however long the label is.
+    ///
+    /// Pages are not kept once sent, so a `RecoveryPolicy` does not apply:
+    /// a printer error stops the job with `Error::JobInterrupted`.
+    ///
+    /// # Arguments
+    /// * `pages` - Iterator of `RowSource`, one per page
+    ///
+    /// # Returns
+    /// * `Ok(())` - Print job completed successfully
+    /// * `Err(Error)` - Same errors as `print`
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType, Printer, Rows};
+    /// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous62))
+    ///     .high_resolution(true);
+    /// let printer = Printer::new(config)?;
+    ///
+    /// // 1 m of 62 mm tape at 600 dpi in the feed direction
+    /// let rows = 23622;
+    /// let banner = Rows::new(rows, (0..rows).map(|y| vec![if y % 600 < 300 { 0xFF } else { 0x00 }; 90]));
+    /// printer.print_rows(vec![banner].into_iter())?;
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn print_rows<R: RowSource>(&self, pages: impl Iterator<Item = R>) -> Result<(), Error> {
+        let _job = self.cancel.job();
+        self.config.validate()?;
+
+        info!("Requesting printer status before streamed print job");
+        self.request_status()?;
+        let status = self.read_status()?;
+        info!("Verifying correct media is installed");
+        status.check_media(self.config.media)?;
+
+        info!("Starting streamed print job");
+        self.print_label(pages.map(Ok), |_| None)
+    }
+
+    // Private helper methods
+
     fn write(&self, buf: Vec<u8>) -> Result<(), Error> {
-        // 動的タイムアウト計算
-        // - ベースタイムアウト: 5秒
-        // - データサイズ依存: 1MB/sの転送速度を仮定
-        // - 安全マージン: 2倍
-        let base_timeout_secs = 5;
-        let transfer_rate_bytes_per_sec = 1_000_000; // 1MB/s
-        let safety_margin = 2.0;
-
-        let data_dependent_timeout =
-            (buf.len() as f64 / transfer_rate_bytes_per_sec as f64) * safety_margin;
-        let total_timeout_secs = base_timeout_secs as f64 + data_dependent_timeout;
-
-        // 最小10秒、最大60秒の範囲でクランプ
-        let timeout_secs = total_timeout_secs.max(10.0).min(60.0);
-        let timeout = Duration::from_secs(timeout_secs as u64);
-
-        debug!(
-            "USB transfer timeout set to {:.1}s for {} bytes",
-            timeout_secs,
-            buf.len()
-        );
-        let result = self
-            .handle
-            .write_bulk(self.endpoint_out.address, &buf, timeout);
-        match result {
-            Ok(n) => {
-                if n == buf.len() {
-                    debug!(
-                        "Successfully wrote {} bytes to endpoint {:#x}",
-                        n, self.endpoint_out.address
-                    );
-                    Ok(())
-                } else {
-                    warn!(
-                        "USB write incomplete: {} of {} bytes transferred (possible timeout)",
-                        n,
-                        buf.len()
-                    );
-                    Err(Error::InvalidResponse(n))
-                }
-            }
-            Err(e) => Err(Error::UsbError(e)),
-        }
+        self.transport.write(&buf)
     }
 
     fn read_status(&self) -> Result<Status, Error> {
         self.read_status_with_timeout(Duration::from_millis(1000))
     }
 
+    /// Read one status, waiting at most `timeout` for it.
+    ///
+    /// Stops waiting with `Error::Cancelled` once the job is cancelled.
     fn read_status_with_timeout(&self, timeout: Duration) -> Result<Status, Error> {
-        let mut buf: [u8; 32] = [0x00; 32];
-        let mut counter = 0;
-
-        debug!("reading from endpoint_in {:#?}", self.endpoint_in);
-        while counter < 100000 {
-            match self
-                .handle
-                .read_bulk(self.endpoint_in.address, &mut buf, timeout)
-            {
+        self.poll_status(timeout, Some(&self.cancel))
+    }
+
+    fn poll_status(&self, timeout: Duration, cancel: Option<&CancelToken>) -> Result<Status, Error> {
+        let mut buf: [u8; STATUS_SIZE] = [0x00; STATUS_SIZE];
+        let started = Instant::now();
+
+        loop {
+            if let Some(cancel) = cancel {
+                if cancel.is_cancelled() {
+                    return Err(Error::Cancelled { completed: 0 });
+                }
+            }
+            // rusbではゼロが無期限を意味するため、最低1msを渡す
+            let remaining = timeout.saturating_sub(started.elapsed()).max(Duration::from_millis(1));
+            match self.transport.read(&mut buf, remaining) {
                 // TODO: Check the first 4bytes match to [0x80, 0x20, 0x42, 0x34]
                 // TODO: Check the error status
                 //
                 // buf is pouplated with 32 bytes of data
-                Ok(32) => {
+                Ok(STATUS_SIZE) => {
                     let status = Status::from_buf(buf);
                     debug!("Raw status code: {:X?}", buf);
                     debug!("Parsed Status struct: {:?}", status);
                     return Ok(status);
                 }
                 Ok(x) => {
-                    debug!("Waiting {counter} {x}");
-                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    debug!("Waiting for status, received {x} bytes");
                 }
-                Err(e) => return Err(Error::UsbError(e)),
+                Err(e) => return Err(e),
             };
-            counter = counter + 1;
+            if started.elapsed() >= timeout {
+                return Err(Error::ReadStatusTimeout);
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
         }
-        Err(Error::ReadStatusTimeout)
     }
 
     fn wait_for_print_completion(&self) -> Result<(), Error> {
         let mut attempts = 0;
-        const MAX_ATTEMPTS: u32 = 100; // 約5秒のタイムアウト
+        // 冷却中はタイムアウトを数えない
+        let mut cooling = false;
 
         debug!("Waiting for print completion...");
 
         loop {
+            self.check_cancelled()?;
             let status = self.read_status_with_timeout(Duration::from_millis(1000))?;
             debug!(
                 "Print completion check: status_type={:?}, phase={:?}, error={:?}",
-                status.status_type, status.phase, status.error
+                status.status_type(),
+                status.phase(),
+                status.errors()
             );
 
+            self.track_cooling(&status, &mut cooling, &mut attempts);
+
             // エラー状態の即座検出
-            if !status.error.is_no_error() {
-                error!("Print operation failed: {:?}", status.error);
-                return Err(Error::PrinterError(status.error));
+            let errors = status.errors();
+            if !errors.is_empty() {
+                if errors.is_fatal() {
+                    error!("Print operation failed with fatal error: {}", errors);
+                } else {
+                    error!("Print operation failed, operator action needed: {}", errors);
+                }
+                return Err(printer_error(errors));
             }
 
-            match (status.status_type, status.phase) {
+            match (status.status_type(), status.phase()) {
                 // エラー状態の即座検出
                 (StatusType::Error, _) => {
                     error!("Printer reported error status");
-                    return Err(Error::PrinterError(status.error));
+                    return Err(Error::PrinterError(status.errors()));
                 }
 
                 // 印刷完了 -> 受信待機への遷移を待つ
@@ -477,13 +750,14 @@ impl Printer {
                     // 完了後、受信状態への遷移を確認
                     std::thread::sleep(Duration::from_millis(100));
                     let final_status = self.read_status_with_timeout(Duration::from_millis(500))?;
-                    if matches!(final_status.phase, Phase::Receiving) {
+                    self.track_cooling(&final_status, &mut cooling, &mut attempts);
+                    if matches!(final_status.phase(), Phase::Receiving) {
                         info!("Print completed, printer ready for next job");
                         return Ok(());
                     }
                     debug!(
                         "Still waiting for transition to receiving state, current phase: {:?}",
-                        final_status.phase
+                        final_status.phase()
                     );
                 }
 
@@ -493,6 +767,11 @@ impl Printer {
                     return Ok(());
                 }
 
+                // 冷却の開始・終了の通知
+                (StatusType::Notification, _) => {
+                    debug!("Notification received: {}", status.notification());
+                }
+
                 // まだ印刷中
                 (StatusType::PhaseChange, Phase::Printing) => {
                     debug!("Print in progress, continuing to monitor");
@@ -507,8 +786,11 @@ impl Printer {
                 }
             }
 
+            if cooling {
+                continue;
+            }
             attempts += 1;
-            if attempts >= MAX_ATTEMPTS {
+            if attempts >= self.config.completion_attempts {
                 error!(
                     "Print completion timed out after {} attempts ({}s)",
                     attempts,
@@ -519,420 +801,708 @@ impl Printer {
         }
     }
 
-    fn initialize(&self) -> Vec<u8> {
-        let mut buf: Vec<u8> = Vec::new();
-        buf.append(&mut [0x00; 400].to_vec());
-        buf.append(&mut [0x1B, 0x40].to_vec());
-        buf
-    }
+    /// Read statuses until `page`, the oldest page sent, is printed.
+    ///
+    /// Later pages may already be buffered by the printer, so the statuses
+    /// of several pages arrive in sequence and `tracker` carries over which
+    /// of them were already counted.
+    fn wait_for_completion(&self, page: usize, tracker: &mut CompletionTracker) -> Result<(), Error> {
+        let mut attempts = 0;
+        let mut cooling = false;
 
-    fn set_media(&self, buf: &mut std::vec::Vec<u8>, raster_count: u32) {
-        buf.extend_from_slice(&[0x1B, 0x69, 0x7A]); // ESC i z
+        loop {
+            self.check_cancelled()?;
+            let status = self.read_status_with_timeout(Duration::from_millis(1000))?;
+            self.track_cooling(&status, &mut cooling, &mut attempts);
 
-        // n1: 有効フラグ (用紙種類+幅+長さ+ラスター数)
-        let valid_flags = 0x02 | 0x04 | 0x08 | 0x40;
-        buf.push(valid_flags);
+            let errors = status.errors();
+            if !errors.is_empty() || status.status_type() == StatusType::Error {
+                error!("Page {} failed: {}", page, errors);
+                return Err(printer_error(errors));
+            }
 
-        // n2: 用紙種類 (長尺:0x0A, ダイカット:0x0C)
-        let media_type = match self.config.media {
-            Media::Continuous(_) => 0x0A,
-            Media::DieCut(_) => 0x0B,
-        };
-        buf.push(media_type);
+            if tracker.page_printed(&status) {
+                self.emit(JobEvent::PagePrinted { page });
+                return Ok(());
+            }
+            debug!("Page {}: {}", page, status);
 
-        // n3, n4: 用紙幅・長さ (mm)
-        let spec = self.config.media.spec();
-        buf.push(spec.width_mm());
-        buf.push(spec.length_mm());
+            if !cooling {
+                attempts += 1;
+                if attempts >= self.config.completion_attempts {
+                    error!("Page {} timed out after {} statuses", page, attempts);
+                    return Err(Error::PrintTimeout);
+                }
+            }
+        }
+    }
 
-        // n5-n8: ラスター数 (リトルエンディアン)
-        let raster_bytes = raster_count.to_le_bytes();
-        buf.extend_from_slice(&raster_bytes);
+    /// Report cooling notifications and restart the timeout once the head has cooled down.
+    fn track_cooling(&self, status: &Status, cooling: &mut bool, attempts: &mut u32) {
+        match status.notification() {
+            Notification::CoolingStarted if !*cooling => {
+                info!("Print head is cooling down, suspending timeout");
+                *cooling = true;
+                self.emit(JobEvent::CoolingStarted);
+            }
+            Notification::CoolingFinished => {
+                info!("Print head cooled down, resuming");
+                *cooling = false;
+                *attempts = 0;
+                self.emit(JobEvent::CoolingFinished);
+            }
+            _ => {}
+        }
+    }
+
+    /// Print `pages`, `replay` copies a page before it is sent so it can be resent after recovery.
+    ///
+    /// A page which failed to be prepared stops the job like a printer error.
+    fn print_label<R: RowSource>(
+        &self,
+        pages: impl Iterator<Item = Result<R, Error>>,
+        replay: impl Fn(&R) -> Option<R>,
+    ) -> Result<(), Error> {
+        let encoder = RasterEncoder::new(self.config.clone())?;
+        let recovery = self.config.recovery;
+        // 復旧待ちに使える残り時間
+        let mut budget = recovery.map(|policy| policy.deadline);
+
+        let (lower, upper) = pages.size_hint();
+        let count = if upper == Some(lower) { Some(lower) } else { None };
+        self.emit(JobEvent::Started { pages: count });
+        let started = Instant::now();
+
+        let mut printed: Vec<usize> = Vec::new();
+        let mut first = true;
+        let mut iter = pages.enumerate().peekable();
+
+        while let Some((index, page)) = iter.next() {
+            if self.cancel.is_cancelled() {
+                return Err(self.cancelled(printed.len()));
+            }
+            let image = match page {
+                Ok(image) => image,
+                Err(err) => return Err(self.interrupted(index, printed, err)),
+            };
+            let last = iter.peek().is_none();
+            // 復旧後に再送できるようにページを保持しておく
+            let retry = recovery.and_then(|_| replay(&image));
+            let mut image = image;
+
+            loop {
+                match self.print_page(&encoder, index, image, first, last) {
+                    Ok(()) => {
+                        self.emit(JobEvent::PagePrinted { page: index });
+                        printed.push(index);
+                        first = false;
+                        break;
+                    }
+                    Err(Error::PrinterError(errors)) if errors.is_recoverable() && retry.is_some() => {
+                        let policy = recovery.expect("retry is only kept with a recovery policy");
+                        warn!(
+                            "Page {} failed with {}, waiting for recovery",
+                            index, errors
+                        );
+                        match self.wait_for_recovery(errors, &policy, &mut budget) {
+                            Ok(()) => {}
+                            Err(Error::Cancelled { .. }) => {
+                                return Err(self.cancelled(printed.len()))
+                            }
+                            Err(err) => return Err(self.interrupted(index, printed, err)),
+                        }
+                        info!("Printer recovered, resuming from page {}", index);
+                        // 初期化からやり直すので先頭ページとして送る
+                        first = true;
+                        image = retry.as_ref().and_then(&replay).unwrap();
+                    }
+                    Err(Error::Cancelled { .. }) => return Err(self.cancelled(printed.len())),
+                    Err(err) => return Err(self.interrupted(index, printed, err)),
+                }
+            }
+        }
 
-        // n9: 先頭ページフラグ (0=先頭ページ)
-        buf.push(0x00);
+        self.emit(JobEvent::Completed {
+            pages: printed.len(),
+            elapsed: started.elapsed(),
+        });
+        Ok(())
+    }
 
-        // n10: 固定値
-        buf.push(0x00);
+    fn check_cancelled(&self) -> Result<(), Error> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled { completed: 0 });
+        }
+        Ok(())
     }
 
-    fn print_label(&self, images: impl Iterator<Item = Matrix>) -> Result<(), Error> {
-        let mut preamble: Vec<u8> = self.initialize();
-        preamble.append(&mut [0x1B, 0x69, 0x61, 0x01].to_vec()); // Set raster command mode
-        preamble.append(&mut [0x1B, 0x69, 0x21, 0x00].to_vec()); // Set auto status notificatoin mode
-                                                                 //
-                                                                 // Apply config values
-        match self.config.clone().build() {
-            Ok(mut buf) => preamble.append(&mut buf),
-            Err(err) => return Err(err),
+    /// Flush the printer buffer after the job was cancelled with `completed` pages printed.
+    fn cancelled(&self, completed: usize) -> Error {
+        info!("Print job cancelled after {} pages", completed);
+        // 受信済みのラスターデータを破棄させる
+        if let Err(err) = self.invalidate() {
+            error!("Failed to reset printer after cancel: {}", err);
         }
+        self.emit(JobEvent::Cancelled { completed });
+        Error::Cancelled { completed }
+    }
 
-        // QL-800では圧縮モードがサポートされていないため、常に非圧縮とする
-        let use_compression = if matches!(self.config.model, Model::QL800) && self.config.compress {
-            warn!("QL-800 does not support compression mode, using uncompressed mode instead");
-            false
-        } else {
-            self.config.compress
+    /// Report the job stopped at `page` and wrap `err` with the pages printed so far.
+    fn interrupted(&self, page: usize, printed: Vec<usize>, err: Error) -> Error {
+        let errors = match &err {
+            Error::PrinterError(errors) => *errors,
+            _ => PrinterErrors::empty(),
         };
-        
-        if use_compression {
-            preamble.append(&mut [0x4D, 0x02].to_vec()); // Set to pack bits compression mode
-        } else {
-            preamble.append(&mut [0x4D, 0x00].to_vec()); // Set to no compression mode
+        self.emit(JobEvent::Failed {
+            page,
+            errors,
+            message: err.to_string(),
+        });
+        Error::JobInterrupted {
+            printed,
+            source: Box::new(err),
+        }
+    }
+
+    fn emit(&self, event: JobEvent) {
+        if let Some(observer) = &self.observer {
+            observer.on_event(&event);
+        }
+    }
+
+    fn print_page(
+        &self,
+        encoder: &RasterEncoder,
+        index: usize,
+        rows: impl RowSource,
+        first: bool,
+        last: bool,
+    ) -> Result<(), Error> {
+        let mut buf: Vec<u8> = Vec::with_capacity(encoder::CHUNK_SIZE);
+        if first {
+            buf.append(&mut encoder.preamble()?);
         }
+        // 長いラベルでもメモリを抑えるため、チャンクごとに送信する
+        let mut send = |chunk: &[u8]| {
+            // 長いページの途中でも止められるように、チャンクごとに確認する
+            self.check_cancelled()?;
+            self.transport.write(chunk)
+        };
+        let result = encoder.stream_page(&mut buf, rows, first, last, encoder::CHUNK_SIZE, &mut send);
+        if let Err(Error::InvalidRows(_)) = &result {
+            // 途中まで送ったページを破棄させる
+            if let Err(err) = self.invalidate() {
+                error!("Failed to reset printer after invalid rows: {}", err);
+            }
+        }
+        result?;
 
-        debug!("{:?}", self.config);
+        if !last {
+            self.emit(JobEvent::PageSent { page: index });
+            info!("Print command sent, waiting for completion...");
 
-        let mut start_flag: bool = true;
-        let mut color = false;
+            // 改善されたステータス待機（中間ページ）
+            self.wait_for_print_completion()?;
+            info!("Page printed successfully");
+        } else {
+            self.emit(JobEvent::PageSent { page: index });
+            info!("Final print command sent, ejecting media...");
 
-        let mut iter = images.into_iter().peekable();
+            // 改善されたステータス待機
+            self.wait_for_print_completion()?;
+            info!("Print job completed successfully");
+
+            self.invalidate()?;
+        }
+        Ok(())
+    }
+
+    /// Poll the status until the error is cleared and the configured media is installed.
+    fn wait_for_recovery(
+        &self,
+        mut errors: PrinterErrors,
+        policy: &RecoveryPolicy,
+        budget: &mut Option<Duration>,
+    ) -> Result<(), Error> {
+        let started = Instant::now();
+        let remaining = budget.unwrap_or_default();
 
         loop {
-            let mut buf: Vec<u8> = Vec::new();
+            if started.elapsed() >= remaining {
+                *budget = Some(Duration::ZERO);
+                error!("Printer did not recover in time: {}", errors);
+                return Err(Error::PrinterError(errors));
+            }
+            std::thread::sleep(policy.poll_interval);
+            self.check_cancelled()?;
+
+            self.request_status()?;
+            let status = self.read_status()?;
+            errors = status.errors();
+            if !errors.is_empty() && !errors.is_recoverable() {
+                return Err(printer_error(errors));
+            }
+            if errors.is_empty() {
+                match status.check_media(self.config.media) {
+                    Ok(()) => break,
+                    Err(err) => debug!("Waiting for the right media: {}", err),
+                }
+            } else {
+                debug!("Waiting for operator: {}", errors);
+            }
+        }
 
-            match iter.next() {
-                Some(image) => {
-                    if start_flag {
-                        buf.append(&mut preamble);
-                    }
+        *budget = Some(remaining.saturating_sub(started.elapsed()));
+        Ok(())
+    }
 
-                    // ESC i z 印刷情報司令
-                    let raster_count = if self.config.two_colors {
-                        (image.len() / 2) as u32
-                    } else {
-                        image.len() as u32
-                    };
-                    self.set_media(&mut buf, raster_count);
-                    if start_flag {
-                        buf.append(&mut [0x00, 0x00].to_vec());
-                        start_flag = false;
-                    } else {
-                        buf.append(&mut [0x01, 0x00].to_vec());
-                    }
+    fn request_status(&self) -> Result<(), Error> {
+        let mut buf: Vec<u8> = encoder::initialize();
+        buf.append(&mut [0x1b, 0x69, 0x53].to_vec());
+        self.write(buf)
+    }
 
-                    // Add raster line image data
-                    if self.config.two_colors {
-                        for mut row in image {
-                            if color {
-                                // Black raster line (color code 0x01)
-                                buf.append(&mut [0x77, 0x01, 90].to_vec());
-                                buf.append(&mut row);
-                                color = !color;
-                            } else {
-                                // Red raster line (color code 0x02)
-                                buf.append(&mut [0x77, 0x02, 90].to_vec());
-                                buf.append(&mut row);
-                                color = !color;
-                            }
-                        }
-                    } else {
-                        if use_compression {
-                            for row in image {
-                                let mut packed = Self::pack_bits(&row);
-                                let len = packed.len() as u8;
-                                buf.append(&mut [0x67, 0x00, len].to_vec());
-                                buf.append(&mut packed);
-                            }
-                        } else {
-                            for mut row in image {
-                                buf.append(&mut [0x67, 0x00, 90].to_vec());
-                                buf.append(&mut row);
-                            }
-                        }
-                    }
+    fn invalidate(&self) -> Result<(), Error> {
+        let buf: Vec<u8> = encoder::initialize();
+        self.write(buf)
+    }
+}
 
-                    if iter.peek().is_some() {
-                        buf.push(0x0C); // FF : Print
-                        self.write(buf)?;
-                        info!("Print command sent, waiting for completion...");
+/// Counts printed pages from the automatic status notifications.
+///
+/// A page is printed at its Completed notification. The phase change back
+/// to receiving which follows belongs to that page, while one without a
+/// Completed notification since the last printing phase counts a page itself.
+#[derive(Debug, Default)]
+struct CompletionTracker {
+    counted: bool,
+}
 
-                        // 改善されたステータス待機（中間ページ）
-                        self.wait_for_print_completion()?;
-                        info!("Page printed successfully");
-                    } else {
-                        buf.push(0x1A); // Control-Z : Print then Eject
-                        self.write(buf)?;
-                        info!("Final print command sent, ejecting media...");
+impl CompletionTracker {
+    /// Whether `status` reports the oldest page in flight as printed.
+    fn page_printed(&mut self, status: &Status) -> bool {
+        match (status.status_type(), status.phase()) {
+            (StatusType::Completed, _) => {
+                self.counted = true;
+                true
+            }
+            (StatusType::PhaseChange, Phase::Receiving) => !std::mem::replace(&mut self.counted, false),
+            (StatusType::PhaseChange, Phase::Printing) => {
+                self.counted = false;
+                false
+            }
+            _ => false,
+        }
+    }
+}
 
-                        // 改善されたステータス待機
-                        self.wait_for_print_completion()?;
-                        info!("Print job completed successfully");
+/// Error for the errors of a status, the cancel key of the printer cancels the job.
+fn printer_error(errors: PrinterErrors) -> Error {
+    if errors.contains(PrinterErrors::CANCEL_KEY) {
+        info!("Cancel key pressed on the printer");
+        Error::Cancelled { completed: 0 }
+    } else {
+        Error::PrinterError(errors)
+    }
+}
 
-                        self.invalidate()?;
-                    }
-                }
-                None => {
-                    break;
-                }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fixture::{default_printer, pattern, printer};
+    use crate::{ContinuousType, DieCutType};
+
+    struct CannedTransport {
+        written: std::cell::RefCell<Vec<u8>>,
+        status: [u8; STATUS_SIZE],
+        // この回数だけ応答し、以降は黙る
+        answers: std::cell::Cell<usize>,
+    }
+
+    impl CannedTransport {
+        // QL-800に29mm長尺テープを装着した状態の応答
+        fn new(answers: usize) -> Self {
+            let mut status = [0u8; STATUS_SIZE];
+            status[..6].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x38, 0x30]);
+            status[10] = 0x1D;
+            status[11] = 0x0A;
+            status[25] = 0x01;
+            CannedTransport {
+                written: std::cell::RefCell::new(Vec::new()),
+                status,
+                answers: std::cell::Cell::new(answers),
             }
         }
-        Ok(())
     }
 
-    /// TIFF PackBits圧縮アルゴリズム（Brother QL仕様準拠）
-    ///
-    /// 仕様:
-    /// - 同一データ連続：個数-1を負数で指定 + データ1バイト
-    /// - 異なるデータ連続：個数-1を正数で指定 + 全データ
-    /// - 90バイト超過時は非圧縮として91バイト送信
-    fn pack_bits(data: &[u8]) -> Vec<u8> {
-        // 入力データが90バイト固定でない場合はそのまま返す
-        if data.len() != 90 {
-            return data.to_vec();
+    impl Transport for CannedTransport {
+        fn write(&self, buf: &[u8]) -> Result<(), Error> {
+            self.written.borrow_mut().extend_from_slice(buf);
+            Ok(())
         }
 
-        let mut packed = Vec::new();
-        let mut i = 0;
+        fn read(&self, buf: &mut [u8; STATUS_SIZE], _timeout: Duration) -> Result<usize, Error> {
+            if self.answers.get() == 0 {
+                return Ok(0);
+            }
+            self.answers.set(self.answers.get() - 1);
+            *buf = self.status;
+            Ok(STATUS_SIZE)
+        }
 
-        while i < data.len() {
-            // Run-length encoding (RLE)のチェック
-            let mut run_length = 1;
-            let run_value = data[i];
+        fn reset(&self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_check_status_with_custom_transport() {
+        let transport = CannedTransport::new(usize::MAX);
+        let media = Media::Continuous(crate::ContinuousType::Continuous29);
+        let config = Config::new(Model::QL800, "serial".to_string(), media);
+        let printer = Printer::with_transport(transport, config);
+
+        let status = printer.check_status().unwrap();
+        assert!(status.check_media(media).is_ok());
+
+        let written = printer.transport.written.borrow();
+        assert_eq!(written.len(), 405);
+        assert_eq!(&written[400..], &[0x1B, 0x40, 0x1B, 0x69, 0x53]);
+    }
 
-            // 同じ値の連続をカウント（最大128個まで）
-            while i + run_length < data.len()
-                && run_length < 128
-                && data[i + run_length] == run_value
-            {
-                run_length += 1;
+    #[test]
+    fn test_cancel_key_cancels_instead_of_recovering() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let policy = RecoveryPolicy::new(Duration::from_secs(5));
+        let (device, printer) = printer(Model::QL820NWB, media, |config| config.recover(policy));
+
+        // 2ページ目の印刷中に本体のキャンセルキーが押される
+        let mut sent = 0;
+        let operator = device.clone();
+        let images = (0..3).map(|_| vec![vec![0xFF; 90]; 4]).inspect(move |_| {
+            sent += 1;
+            if sent == 3 {
+                operator.raise_errors(PrinterErrors::CANCEL_KEY);
             }
+        });
+        let started = Instant::now();
+        match printer.print(images) {
+            Err(Error::Cancelled { completed }) => assert_eq!(completed, 1),
+            other => panic!("{:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(device.pages().len(), 1);
+    }
 
-            // RLEが効果的な場合（2個以上の連続）
-            if run_length >= 2 {
-                // 負数で圧縮指示: -(count-1)
-                packed.push((-(run_length as i8 - 1)) as u8);
-                packed.push(run_value);
-                i += run_length;
-            } else {
-                // リテラル実行のチェック
-                let start_pos = i;
-                let mut literal_length = 1;
-
-                // リテラル実行の最適な長さを決定
-                while i + literal_length < data.len() && literal_length < 128 {
-                    // 次の位置で2個以上同じ値が続く場合は、ここでリテラル実行を終了
-                    if i + literal_length + 1 < data.len()
-                        && data[i + literal_length] == data[i + literal_length + 1]
-                    {
-                        break;
-                    }
-                    literal_length += 1;
-                }
+    /// Simulator holding back every status once a page is printed, until `released`.
+    struct Gated {
+        device: crate::SimulatedPrinter,
+        released: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl Transport for Gated {
+        fn write(&self, buf: &[u8]) -> Result<(), Error> {
+            self.device.write(buf)
+        }
 
-                // リテラル実行: 正数で非圧縮指示
-                packed.push((literal_length - 1) as u8);
-                packed.extend_from_slice(&data[start_pos..start_pos + literal_length]);
-                i += literal_length;
+        fn read(&self, buf: &mut [u8; STATUS_SIZE], timeout: Duration) -> Result<usize, Error> {
+            let released = self.released.load(std::sync::atomic::Ordering::SeqCst);
+            if !released && !self.device.pages().is_empty() {
+                return Ok(0);
             }
+            self.device.read(buf, timeout)
         }
 
-        // 重要な最適化: 90バイト超過時は非圧縮として91バイト返す
-        if packed.len() > 90 {
-            warn!(
-                "Data compression ineffective, sending uncompressed ({} bytes)",
-                data.len()
-            );
-            let mut result = Vec::with_capacity(91);
-            result.push(89); // 90-1 = 89（90バイトの非圧縮指示）
-            result.extend_from_slice(data);
-            result
-        } else {
-            debug!(
-                "Compression reduced data from {} to {} bytes ({:.1}% reduction)",
-                data.len(),
-                packed.len(),
-                (1.0 - packed.len() as f64 / data.len() as f64) * 100.0
-            );
-            packed
+        fn reset(&self) -> Result<(), Error> {
+            self.device.reset()
         }
     }
 
-    fn request_status(&self) -> Result<(), Error> {
-        let mut buf: Vec<u8> = self.initialize();
-        buf.append(&mut [0x1b, 0x69, 0x53].to_vec());
-        self.write(buf)
+    #[test]
+    fn test_pipelined_sends_while_printing() {
+        let media = Media::DieCut(crate::DieCutType::DieCut29x90);
+        let device = crate::SimulatedPrinter::new(Model::QL820NWB, Some(media));
+        let released = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let transport = Gated {
+            device: device.clone(),
+            released: released.clone(),
+        };
+        let config = Config::new(Model::QL820NWB, "serial".to_string(), media);
+        let mut printer = Printer::with_transport(transport, config);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        printer.set_observer(sender);
+
+        // 1ページ目の完了通知は3ページ目のエンコードまで届かない
+        let pages: Vec<Matrix> = (0..4).map(|n| vec![vec![n as u8; 90]; 3]).collect();
+        let images = pages.clone().into_iter().enumerate().map(move |(index, page)| {
+            if index == 2 {
+                released.store(true, std::sync::atomic::Ordering::SeqCst);
+            }
+            page
+        });
+        let report = printer.print_pipelined(images).unwrap();
+        assert_eq!(report.pages, 4);
+        assert_eq!(device.pages(), pages);
+
+        let events: Vec<JobEvent> = receiver.try_iter().collect();
+        let position = |event: JobEvent| events.iter().position(|e| *e == event).unwrap();
+        // 2ページ目は1ページ目の印刷完了を待たずに送られる
+        assert!(position(JobEvent::PageSent { page: 1 }) < position(JobEvent::PagePrinted { page: 0 }));
+        let printed: Vec<usize> = events
+            .iter()
+            .filter_map(|event| match event {
+                JobEvent::PagePrinted { page } => Some(*page),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(printed, vec![0, 1, 2, 3]);
     }
 
-    fn invalidate(&self) -> Result<(), Error> {
-        let buf: Vec<u8> = self.initialize();
-        self.write(buf)
+    #[test]
+    fn test_completion_tracker_counts_notifications() {
+        let status = |status_type: u8, phase: u8| {
+            let mut buf = [0u8; STATUS_SIZE];
+            buf[18] = status_type;
+            buf[19] = phase;
+            Status::from_buf(buf)
+        };
+        let (printing, completed, receiving) = (status(0x06, 0x01), status(0x01, 0x01), status(0x06, 0x00));
+        let count = |statuses: &[&Status]| {
+            let mut tracker = CompletionTracker::default();
+            statuses.iter().filter(|status| tracker.page_printed(status)).count()
+        };
+
+        // 完了通知の後の受信状態への移行は同じページ
+        assert_eq!(count(&[&printing, &completed, &receiving, &printing, &completed, &receiving]), 2);
+        // 受信状態に戻らず、続けて次のページを印刷する
+        assert_eq!(count(&[&printing, &completed, &printing, &completed, &receiving]), 2);
+        // 完了通知がなければ受信状態への移行で数える
+        assert_eq!(count(&[&printing, &receiving, &printing, &receiving]), 2);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_pipelined_rejects_recovery_policy() {
+        let media = Media::DieCut(DieCutType::DieCut29x90);
+        let policy = RecoveryPolicy::new(Duration::from_secs(5));
+        let (device, printer) = printer(Model::QL820NWB, media, |config| config.recover(policy));
+
+        let result = printer.print_pipelined(vec![vec![vec![0xFF; 90]; 3]].into_iter());
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+        assert!(device.pages().is_empty());
+    }
 
     #[test]
-    fn test_pack_bits_compression() {
-        // テスト1: 効果的な圧縮（同一データ連続）
-        let all_zeros = vec![0u8; 90];
-        let compressed = Printer::pack_bits(&all_zeros);
-        println!(
-            "All zeros: {} -> {} bytes",
-            all_zeros.len(),
-            compressed.len()
-        );
-        assert!(compressed.len() < all_zeros.len(), "圧縮が効果的でない");
-
-        // テスト2: 非効果的な圧縮（ランダムデータ）
-        let random_data: Vec<u8> = (0..90).map(|i| (i * 37 + 17) as u8).collect();
-        let compressed_random = Printer::pack_bits(&random_data);
-        println!(
-            "Random data: {} -> {} bytes",
-            random_data.len(),
-            compressed_random.len()
-        );
+    fn test_cancel_while_waiting_for_status() {
+        // 印刷前の問い合わせにだけ応答し、印刷完了の通知が来ない
+        let media = Media::Continuous(crate::ContinuousType::Continuous29);
+        let config = Config::new(Model::QL800, "serial".to_string(), media);
+        let printer = Printer::with_transport(CannedTransport::new(1), config);
+
+        let token = printer.cancel_token();
+        let operator = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            token.cancel();
+        });
+        let started = Instant::now();
+        let result = printer.print(vec![vec![vec![0xFF; 90]; 4]].into_iter());
+        assert!(matches!(result, Err(Error::Cancelled { completed: 0 })), "{:?}", result);
+        // 1秒の読み取りタイムアウトを待たずに止まる
+        assert!(started.elapsed() < Duration::from_millis(500));
+        operator.join().unwrap();
+    }
 
-        // テスト3: 91バイト制限の確認
-        if compressed_random.len() > 90 {
-            println!("91バイト制限により非圧縮データが返される");
-            assert_eq!(compressed_random.len(), 91); // 89 + 90バイトの元データ
-            assert_eq!(compressed_random[0], 89); // 非圧縮指示
-        }
+    #[test]
+    fn test_cancel_before_print() {
+        let (device, printer) = default_printer();
+
+        // キューで待っている間に取り消されたジョブ
+        printer.cancel_token().cancel();
+        let result = printer.print(vec![pattern(2)].into_iter());
+        assert!(matches!(result, Err(Error::Cancelled { completed: 0 })), "{:?}", result);
+        assert!(device.pages().is_empty());
+
+        // 取り消しは終了したジョブと共に消える
+        assert!(!printer.cancel_token().is_cancelled());
+        printer.print(vec![pattern(2)].into_iter()).unwrap();
+        assert_eq!(device.pages().len(), 1);
+    }
 
-        // テスト4: 混合パターン（部分的な圧縮効果）
-        let mut mixed_data = vec![0u8; 30];
-        mixed_data.extend(vec![255u8; 30]);
-        mixed_data.extend((0..30).map(|i| i as u8));
-        let compressed_mixed = Printer::pack_bits(&mixed_data);
-        println!(
-            "Mixed data: {} -> {} bytes",
-            mixed_data.len(),
-            compressed_mixed.len()
-        );
+    #[test]
+    fn test_cancel_within_long_page() {
+        let (device, printer) = default_printer();
+
+        // 20000行のうち1000行目でキャンセルする
+        let token = printer.cancel_token();
+        let pulled = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = pulled.clone();
+        let rows = (0..20000).map(move |i| {
+            if i == 1000 {
+                token.cancel();
+            }
+            counter.set(i + 1);
+            vec![0xFF; 90]
+        });
+        let result = printer.print_rows(vec![Rows::new(20000, rows)].into_iter());
+        assert!(matches!(result, Err(Error::Cancelled { completed: 0 })), "{:?}", result);
+        // 次のチャンクの送信前に止まる
+        assert!(pulled.get() < 1000 + encoder::CHUNK_SIZE / 90);
+        assert!(device.pages().is_empty());
     }
 
     #[test]
-    fn test_pack_bits_edge_cases() {
-        // エッジケース1: 空のデータ
-        let empty_data = vec![];
-        let compressed_empty = Printer::pack_bits(&empty_data);
-        assert_eq!(compressed_empty, empty_data);
-
-        // エッジケース2: 90バイト以外のサイズ
-        let wrong_size = vec![42u8; 50];
-        let compressed_wrong = Printer::pack_bits(&wrong_size);
-        assert_eq!(compressed_wrong, wrong_size);
-
-        // エッジケース3: 単一バイトの繰り返し（最大圧縮）
-        let single_byte = vec![42u8; 90];
-        let compressed_single = Printer::pack_bits(&single_byte);
-        assert_eq!(compressed_single.len(), 2); // 長さ指示 + データ
-        assert_eq!(compressed_single[0], (-(90i8 - 1)) as u8); // -89
-        assert_eq!(compressed_single[1], 42);
+    fn test_print_wide() {
+        let media = Media::DieCut(DieCutType::DieCut102x152);
+        let (device, printer) = printer(Model::QL1110NWB, media, |config| config.compress(true));
+
+        let page: Matrix = (0..5)
+            .map(|y| (0..162).map(|x| ((x / 3 + y) % 4 * 85) as u8).collect())
+            .collect();
+        printer.print(vec![page.clone()].into_iter()).unwrap();
+
+        assert_eq!(device.pages(), vec![page]);
     }
-}
 
-///
-/// Status received from the printer encoded to Rust friendly type.
-///
-#[derive(Debug)]
-#[allow(dead_code)]
-pub struct Status {
-    model: Model,
-    error: PrinterError,
-    media: Option<Media>,
-    mode: u8,
-    status_type: StatusType,
-    phase: Phase,
-    notification: Notification,
-    id: u8,
-}
+    #[test]
+    fn test_unsupported_config_rejected_up_front() {
+        let media = Media::Continuous(ContinuousType::Continuous102);
+        let (device, printer) = printer(Model::QL820NWB, media, |config| config);
+
+        let result = printer.print(vec![pattern(2)].into_iter());
+        assert!(matches!(result, Err(Error::InvalidConfig(_))));
+        let message = result.unwrap_err().to_string();
+        assert!(message.starts_with("Invalid configuration: QL-820NWB does not support"), "{}", message);
+        assert_eq!(device.raster_count(), 0);
+        assert!(device.pages().is_empty());
+    }
 
-impl Status {
-    fn from_buf(buf: [u8; 32]) -> Self {
-        Status {
-            model: Model::from_code(buf[4]),
-            error: PrinterError::from_buf(buf),
-            media: Media::from_buf(buf),
-            mode: buf[15],
-            status_type: StatusType::from_code(buf[18]),
-            phase: Phase::from_buf(buf),
-            notification: Notification::from_code(buf[22]),
-            id: buf[14],
-        }
+    #[test]
+    fn test_recover_and_resume() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let policy =
+            RecoveryPolicy::new(Duration::from_secs(5)).poll_interval(Duration::from_millis(20));
+        let (device, printer) = printer(Model::QL820NWB, media, |config| config.recover(policy));
+
+        // 2ページ目の印刷中にカバーが開き、しばらくして閉じられる
+        // (最終ページ判定のため次のページが先に取り出される)
+        let pages = vec![pattern(3), pattern(4), pattern(5)];
+        let mut sent = 0;
+        let operator = device.clone();
+        let images = pages.clone().into_iter().inspect(move |_| {
+            sent += 1;
+            if sent == 3 {
+                operator.raise_errors(PrinterErrors::COVER_OPEN);
+                let operator = operator.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(100));
+                    operator.clear_errors();
+                });
+            }
+        });
+        printer.print(images).unwrap();
+
+        assert_eq!(device.pages(), pages);
     }
 
-    pub fn check_media(self, expected_media: Media) -> Result<(), Error> {
-        match self.media {
-            Some(actual_media) => {
-                if actual_media == expected_media {
-                    Ok(())
-                } else {
-                    Err(Error::MediaMismatch {
-                        expected: expected_media,
-                        actual: actual_media,
-                    })
+    #[test]
+    fn test_interrupted_without_recovery() {
+        let (device, printer) = default_printer();
+
+        let operator = device.clone();
+        let images = vec![pattern(3), pattern(4), pattern(5)]
+            .into_iter()
+            .enumerate()
+            .map(move |(i, page)| {
+                // 2ページ目の印刷前に取り出される
+                if i == 2 {
+                    operator.raise_errors(PrinterErrors::END_OF_MEDIA);
                 }
+                page
+            });
+        match printer.print(images) {
+            Err(Error::JobInterrupted { printed, source }) => {
+                assert_eq!(printed, vec![0]);
+                assert!(
+                    matches!(*source, Error::PrinterError(e) if e == PrinterErrors::END_OF_MEDIA)
+                );
             }
-            None => Err(Error::NoMediaInstalled),
+            other => panic!("unexpected result {:?}", other),
         }
+        assert_eq!(device.pages().len(), 1);
     }
-}
 
-// StatusType
-
-#[derive(Debug, PartialEq, Clone, Copy)]
-enum StatusType {
-    ReplyToRequest,
-    Completed,
-    Error,
-    Offline,
-    Notification,
-    PhaseChange,
-    Unknown,
-}
+    #[test]
+    fn test_cooling_suspends_timeout() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (device, mut printer) =
+            printer(Model::QL820NWB, media, |config| config.completion_attempts(10));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        printer.set_observer(sender);
+
+        // タイムアウトの上限より多くのステータスを冷却中に受け取る
+        device.cool_down_next_page(15);
+        printer.print(vec![pattern(2), pattern(3)].into_iter()).unwrap();
+        assert_eq!(device.pages().len(), 2);
+
+        let events: Vec<JobEvent> = receiver.try_iter().collect();
+        let cooling: Vec<&JobEvent> = events
+            .iter()
+            .filter(|event| matches!(event, JobEvent::CoolingStarted | JobEvent::CoolingFinished))
+            .collect();
+        assert_eq!(cooling, vec![&JobEvent::CoolingStarted, &JobEvent::CoolingFinished]);
+    }
 
-impl StatusType {
-    fn from_code(code: u8) -> StatusType {
-        match code {
-            0x00 => Self::ReplyToRequest,
-            0x01 => Self::Completed,
-            0x02 => Self::Error,
-            0x04 => Self::Offline,
-            0x05 => Self::Notification,
-            0x06 => Self::PhaseChange,
-            _ => Self::Unknown,
-        }
+    #[test]
+    fn test_print_pipelined() {
+        let media = Media::DieCut(DieCutType::DieCut29x90);
+        let (device, mut printer) = printer(Model::QL820NWB, media, |config| config.compress(true));
+        let (sender, receiver) = std::sync::mpsc::channel();
+        printer.set_observer(sender);
+
+        let pages: Vec<Matrix> = (1..=5).map(pattern).collect();
+        let report = printer.print_pipelined(pages.clone().into_iter()).unwrap();
+
+        assert_eq!(device.pages(), pages);
+        assert_eq!(report.pages, 5);
+        assert!(report.labels_per_minute > 0.0);
+        let printed: Vec<JobEvent> = receiver
+            .try_iter()
+            .filter(|event| matches!(event, JobEvent::PagePrinted { .. }))
+            .collect();
+        assert_eq!(printed.len(), 5);
     }
-}
-// Phase
-
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Phase {
-    Receiving,
-    Printing,
-    Waiting(u16),
-    // Printing(u16),
-}
 
-impl Phase {
-    fn from_buf(buf: [u8; 32]) -> Self {
-        match buf[19] {
-            0x00 => Self::Receiving,
-            0x01 => Self::Printing,
-            _ => Self::Waiting(0),
-        }
+    #[test]
+    fn test_print_pipelined_without_auto_status() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (device, printer) = printer(Model::QL570, media, |config| config);
+
+        let report = printer.print_pipelined(vec![pattern(2), pattern(3)].into_iter()).unwrap();
+        assert_eq!(report.pages, 2);
+        assert_eq!(device.pages().len(), 2);
     }
 }
 
-// Notification
-
-#[derive(Debug)]
-enum Notification {
-    NotAvailable,
-    CoolingStarted,
-    CoolingFinished,
+/// Recovery from recoverable printer errors, see `Config::recover`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecoveryPolicy {
+    pub(crate) deadline: Duration,
+    pub(crate) poll_interval: Duration,
 }
 
-impl Notification {
-    fn from_code(code: u8) -> Self {
-        match code {
-            0x03 => Self::CoolingStarted,
-            0x04 => Self::CoolingFinished,
-            _ => Self::NotAvailable,
+impl RecoveryPolicy {
+    /// Wait at most `deadline` in total over the whole job for errors to be cleared.
+    pub fn new(deadline: Duration) -> Self {
+        RecoveryPolicy {
+            deadline,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+
+    /// Interval between status requests while waiting, 500ms by default.
+    pub fn poll_interval(self, poll_interval: Duration) -> Self {
+        RecoveryPolicy {
+            poll_interval,
+            ..self
         }
     }
 }
@@ -947,15 +1517,17 @@ enum AutoCut {
 
 #[derive(Debug, Clone)]
 pub struct Config {
-    model: Model,
-    serial: String,
-    media: Media,
+    pub(crate) model: Model,
+    pub(crate) serial: String,
+    pub(crate) media: Media,
     auto_cut: AutoCut,
-    two_colors: bool,
-    cut_at_end: bool,
-    high_resolution: bool,
-    feed: u16,
-    compress: bool,
+    pub(crate) two_colors: bool,
+    pub(crate) cut_at_end: bool,
+    pub(crate) high_resolution: bool,
+    pub(crate) feed: u16,
+    pub(crate) compress: bool,
+    pub(crate) recovery: Option<RecoveryPolicy>,
+    pub(crate) completion_attempts: u32,
 }
 
 impl Config {
@@ -966,7 +1538,7 @@ impl Config {
     /// # Example
     ///
     /// ```rust,no_run
-    /// use ptouch::{Config, ContinuousType, Media, Model};
+    /// use ql_label::{Config, ContinuousType, Media, Model};
     /// 
     /// let media = Media::Continuous(ContinuousType::Continuous29);
     /// let model = Model::QL800;
@@ -974,16 +1546,24 @@ impl Config {
     /// ```
     ///
     pub fn new(model: Model, serial: String, media: Media) -> Config {
+        // カッターのない機種ではカットを無効にしておく
+        let cutter = model.capabilities().cutter;
         Config {
             model,
             serial,
             media,
-            auto_cut: AutoCut::Enabled(1),
+            auto_cut: if cutter {
+                AutoCut::Enabled(1)
+            } else {
+                AutoCut::Disabled
+            },
             two_colors: false,
-            cut_at_end: true,
+            cut_at_end: cutter,
             high_resolution: false,
             feed: media.get_default_feed_dots(),
             compress: false,
+            recovery: None,
+            completion_attempts: MAX_ATTEMPTS,
         }
     }
 
@@ -994,7 +1574,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62))
     ///     .enable_auto_cut(3); // Cut after every 3 labels
@@ -1012,7 +1592,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62))
     ///     .disable_auto_cut();
@@ -1031,7 +1611,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62))
     ///     .cut_at_end(true); // Cut at the end of job
@@ -1053,7 +1633,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62))
     ///     .high_resolution(true); // Enable 600 DPI
@@ -1075,7 +1655,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62))
     ///     .set_feed_in_dots(150); // Set feed to 150 dots
@@ -1094,7 +1674,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62Red))
     ///     .two_colors(true); // Enable red and black printing
@@ -1113,7 +1693,7 @@ impl Config {
     ///
     /// # Example
     /// ```rust,no_run
-    /// # use ptouch::{Config, Model, Media, ContinuousType};
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
     /// let config = Config::new(Model::QL820NWB, "serial".to_string(), 
     ///                         Media::Continuous(ContinuousType::Continuous62))
     ///     .compress(true); // Enable compression
@@ -1125,7 +1705,116 @@ impl Config {
         }
     }
 
-    fn build(self) -> Result<Vec<u8>, Error> {
+    /// Recover from errors an operator can fix instead of failing the job.
+    ///
+    /// When the printer reports a recoverable error such as cover open or end
+    /// of media, the status is polled until the condition clears and the
+    /// configured media is installed. The job is then re-initialized and
+    /// resumed from the first unprinted page.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType, RecoveryPolicy};
+    /// # use std::time::Duration;
+    /// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous62))
+    ///     .recover(RecoveryPolicy::new(Duration::from_secs(300)));
+    /// ```
+    pub fn recover(self, policy: RecoveryPolicy) -> Self {
+        Config {
+            recovery: Some(policy),
+            ..self
+        }
+    }
+
+    /// Give up on a page with `Error::PrintTimeout` after `attempts` statuses without completion.
+    ///
+    /// The default of 100 is about 5 seconds. Statuses received while the
+    /// print head cools down are not counted.
+    ///
+    /// # Example
+    /// ```rust,no_run
+    /// # use ql_label::{Config, Model, Media, ContinuousType};
+    /// let config = Config::new(Model::QL820NWB, "serial".to_string(),
+    ///                         Media::Continuous(ContinuousType::Continuous62))
+    ///     .completion_attempts(400); // Long labels
+    /// ```
+    pub fn completion_attempts(self, attempts: u32) -> Self {
+        Config {
+            completion_attempts: attempts,
+            ..self
+        }
+    }
+
+    /// Labels between cuts, `None` when auto cut is disabled.
+    pub(crate) fn auto_cut_size(&self) -> Option<u8> {
+        match self.auto_cut {
+            AutoCut::Enabled(size) => Some(size),
+            AutoCut::Disabled => None,
+        }
+    }
+
+    /// Check the configuration against `Model::capabilities`.
+    ///
+    /// Compression is not checked, it is silently turned off for models
+    /// without support for it.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The model can print with this configuration
+    /// * `Err(Error::InvalidConfig)` - Description of the first unsupported setting
+    ///
+    /// # Example
+    /// ```rust
+    /// # use ql_label::{Config, ContinuousType, Error, Media, Model};
+    /// let config = Config::new(Model::QL720NW, "serial".to_string(),
+    ///                          Media::Continuous(ContinuousType::Continuous62Red))
+    ///     .two_colors(true);
+    /// assert!(matches!(config.validate(), Err(Error::InvalidConfig(_))));
+    /// ```
+    pub fn validate(&self) -> Result<(), Error> {
+        let capabilities = self.model.capabilities();
+
+        if !capabilities.supports_media(self.media) {
+            return Err(Error::InvalidConfig(format!(
+                "{} does not support {:?}",
+                self.model, self.media
+            )));
+        }
+        if self.two_colors {
+            if !capabilities.two_colors {
+                return Err(Error::InvalidConfig(format!(
+                    "{} does not support two-color printing",
+                    self.model
+                )));
+            }
+            if self.media != Media::Continuous(crate::media::ContinuousType::Continuous62Red) {
+                return Err(Error::InvalidConfig(format!(
+                    "Two-color printing requires red/black media, not {:?}",
+                    self.media
+                )));
+            }
+        }
+        if self.high_resolution && !capabilities.high_resolution {
+            return Err(Error::InvalidConfig(format!(
+                "{} does not support high resolution printing",
+                self.model
+            )));
+        }
+        if !capabilities.cutter && (matches!(self.auto_cut, AutoCut::Enabled(_)) || self.cut_at_end) {
+            return Err(Error::InvalidConfig(format!(
+                "{} has no cutter, disable auto cut and cut at end",
+                self.model
+            )));
+        }
+        self.media
+            .check_feed_value(self.feed)
+            .map_err(Error::InvalidConfig)?;
+        Ok(())
+    }
+
+    pub(crate) fn build(self) -> Result<Vec<u8>, Error> {
+        self.validate()?;
+
         let mut buf: Vec<u8> = Vec::new();
 
         // Set feeding values in dots
@@ -1144,7 +1833,7 @@ impl Config {
             let mut auto_cut_num: u8 = 1;
 
             if let AutoCut::Enabled(n) = self.auto_cut {
-                various_mode = various_mode | 0b0100_0000;
+                various_mode |= 0b0100_0000;
                 auto_cut_num = n;
             }
 
@@ -1159,15 +1848,15 @@ impl Config {
             let mut expanded_mode: u8 = 0b00000000;
 
             if self.two_colors {
-                expanded_mode = expanded_mode | 0b0000_0001;
+                expanded_mode |= 0b0000_0001;
             }
 
             if self.cut_at_end {
-                expanded_mode = expanded_mode | 0b0000_1000;
+                expanded_mode |= 0b0000_1000;
             };
 
             if self.high_resolution {
-                expanded_mode = expanded_mode | 0b0100_0000;
+                expanded_mode |= 0b0100_0000;
             }
 
             debug!("Print mode settings: {:#04x}", expanded_mode);
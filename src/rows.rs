@@ -0,0 +1,150 @@
+//! Pages produced one raster line at a time.
+//!
+//! A `Matrix` holds a whole page in memory. A `RowSource` instead declares
+//! the number of raster lines up front and yields them on demand, so very
+//! long continuous labels can be rendered while they are sent.
+
+use crate::Matrix;
+
+/// One raster line, 1-bit pixels packed 8 per byte.
+pub type Row = Vec<u8>;
+
+/// Page whose raster lines are produced on demand.
+///
+/// `raster_count` is announced to the printer before the first line is
+/// sent, so it must be known in advance. Every line must be as wide as the
+/// model's raster line. A source ending early, yielding more lines or a
+/// line of the wrong width fails the page with `Error::InvalidRows`.
+///
+/// For two-color printing the lines alternate between black and red, like
+/// `TwoColorMatrix::to_alternating_matrix`, and `raster_count` counts both.
+pub trait RowSource {
+    /// Number of lines the source yields.
+    fn raster_count(&self) -> u32;
+
+    /// Next line, `None` once the page is complete.
+    fn next_row(&mut self) -> Option<Row>;
+}
+
+/// `RowSource` made of a raster count and an iterator of lines.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{ContinuousType, Media, Model, Rows, SimulatedPrinter};
+/// # let media = Media::Continuous(ContinuousType::Continuous62);
+/// # let (device, printer) = SimulatedPrinter::connect(Model::QL820NWB, media);
+/// // A 1 m banner, rendered line by line while it is sent
+/// let banner = Rows::new(11811, (0..11811).map(|y| vec![if y % 100 < 50 { 0xFF } else { 0x00 }; 90]));
+/// printer.print_rows(vec![banner].into_iter())?;
+///
+/// assert_eq!(device.pages()[0].len(), 11811);
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Rows<I> {
+    raster_count: u32,
+    rows: I,
+}
+
+impl<I: Iterator<Item = Row>> Rows<I> {
+    /// # Arguments
+    /// * `raster_count` - Number of lines `rows` yields
+    /// * `rows` - Raster lines, from top to bottom of the label
+    pub fn new(raster_count: u32, rows: I) -> Self {
+        Rows { raster_count, rows }
+    }
+}
+
+impl From<Matrix> for Rows<std::vec::IntoIter<Row>> {
+    fn from(image: Matrix) -> Self {
+        Rows::new(image.len() as u32, image.into_iter())
+    }
+}
+
+impl<I: Iterator<Item = Row>> RowSource for Rows<I> {
+    fn raster_count(&self) -> u32 {
+        self.raster_count
+    }
+
+    fn next_row(&mut self) -> Option<Row> {
+        self.rows.next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fixture::{default_printer, pattern};
+    use crate::{encoder, Config, ContinuousType, Error, Media, Model, Printer, SimulatedPrinter};
+    use crate::{Transport, STATUS_SIZE};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Transport recording the size of the largest write.
+    struct Recording {
+        device: SimulatedPrinter,
+        largest: Arc<Mutex<usize>>,
+    }
+
+    impl Transport for Recording {
+        fn write(&self, buf: &[u8]) -> Result<(), Error> {
+            let mut largest = self.largest.lock().unwrap();
+            *largest = (*largest).max(buf.len());
+            self.device.write(buf)
+        }
+
+        fn read(&self, buf: &mut [u8; STATUS_SIZE], timeout: Duration) -> Result<usize, Error> {
+            self.device.read(buf, timeout)
+        }
+
+        fn reset(&self) -> Result<(), Error> {
+            self.device.reset()
+        }
+    }
+
+    #[test]
+    fn test_print_rows_in_chunks() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let device = SimulatedPrinter::new(Model::QL820NWB, Some(media));
+        let largest = Arc::new(Mutex::new(0));
+        let transport = Recording {
+            device: device.clone(),
+            largest: largest.clone(),
+        };
+        let config = Config::new(Model::QL820NWB, "serial".to_string(), media);
+        let printer = Printer::with_transport(transport, config);
+
+        // 約1mの長尺ラベル (約1MB)
+        let rows = 12000;
+        let banner = Rows::new(rows as u32, pattern(rows).into_iter());
+        printer.print_rows(vec![banner].into_iter()).unwrap();
+
+        assert_eq!(device.raster_count(), rows as u32);
+        assert_eq!(device.pages(), vec![pattern(rows)]);
+        let largest = *largest.lock().unwrap();
+        assert!(largest < encoder::CHUNK_SIZE + 200, "{} bytes in one write", largest);
+    }
+
+    #[test]
+    fn test_row_source_must_match_raster_count() {
+        let (device, printer) = default_printer();
+
+        let short = Rows::new(10, pattern(4).into_iter());
+        let long = Rows::new(4, pattern(10).into_iter());
+        let narrow = Rows::new(4, vec![vec![0xFF; 20]; 4].into_iter());
+        for rows in [short, long, narrow] {
+            match printer.print_rows(vec![rows].into_iter()) {
+                Err(Error::JobInterrupted { source, .. }) => {
+                    assert!(matches!(*source, Error::InvalidRows(_)), "{:?}", source)
+                }
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+        assert!(device.pages().is_empty());
+
+        // 破棄した後も次のジョブは印刷できる
+        let rows = Rows::new(4, pattern(4).into_iter());
+        printer.print_rows(vec![rows].into_iter()).unwrap();
+        assert_eq!(device.pages(), vec![pattern(4)]);
+    }
+}
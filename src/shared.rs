@@ -0,0 +1,274 @@
+//! Printer shared between threads.
+//!
+//! A `Printer` must only be driven by one thread at a time, otherwise the
+//! raster data of two jobs gets interleaved. `SharedPrinter` hands the
+//! printer to an actor thread which runs the submitted jobs one after the
+//! other.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+use log::debug;
+
+use crate::{
+    cancel::CancelToken, error::Error, printer::Printer, status::Status, transport::Transport,
+    utils::TwoColorMatrix, Matrix,
+};
+
+enum Job {
+    Print(Vec<Matrix>, Completer<()>),
+    PrintTwoColor(Vec<TwoColorMatrix>, Completer<()>),
+    CheckStatus(Completer<Status>),
+}
+
+/// Handle to a printer usable from any number of threads.
+///
+/// Clones refer to the same printer. Jobs are queued and printed in the
+/// order they were submitted, each submission returns a `JobHandle`.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{ContinuousType, Media, Model, SharedPrinter, SimulatedPrinter};
+/// # let media = Media::Continuous(ContinuousType::Continuous62);
+/// # let (device, printer) = SimulatedPrinter::connect(Model::QL820NWB, media);
+/// let printer = SharedPrinter::new(printer);
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|_| {
+///         let printer = printer.clone();
+///         std::thread::spawn(move || printer.submit(vec![vec![vec![0xFF; 90]; 10]]).join())
+///     })
+///     .collect();
+/// for handle in handles {
+///     handle.join().unwrap()?;
+/// }
+/// assert_eq!(device.pages().len(), 4);
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct SharedPrinter {
+    jobs: mpsc::Sender<Job>,
+    cancel: CancelToken,
+}
+
+impl SharedPrinter {
+    /// Start the actor thread owning `printer`.
+    ///
+    /// The thread stops once every clone is dropped and the queued jobs are done.
+    pub fn new<T>(printer: Printer<T>) -> Self
+    where
+        T: Transport + Send + 'static,
+    {
+        let (jobs, receiver) = mpsc::channel::<Job>();
+        let cancel = printer.cancel_token();
+        thread::Builder::new()
+            .name("ql-label-printer".to_string())
+            .spawn(move || {
+                for job in receiver {
+                    match job {
+                        Job::Print(images, completer) => {
+                            completer.complete(printer.print(images.into_iter()))
+                        }
+                        Job::PrintTwoColor(images, completer) => {
+                            completer.complete(printer.print_two_color(images.into_iter()))
+                        }
+                        Job::CheckStatus(completer) => completer.complete(printer.check_status()),
+                    }
+                }
+                debug!("Printer actor thread stopped");
+            })
+            .expect("failed to spawn printer thread");
+
+        SharedPrinter { jobs, cancel }
+    }
+
+    /// Queue single-color labels, see `Printer::print`.
+    pub fn submit(&self, images: impl IntoIterator<Item = Matrix>) -> JobHandle<()> {
+        self.queue(|completer| Job::Print(images.into_iter().collect(), completer))
+    }
+
+    /// Queue two-color labels, see `Printer::print_two_color`.
+    pub fn submit_two_color(&self, images: impl IntoIterator<Item = TwoColorMatrix>) -> JobHandle<()> {
+        self.queue(|completer| Job::PrintTwoColor(images.into_iter().collect(), completer))
+    }
+
+    /// Queue a status request, answered once the jobs queued before it are done.
+    pub fn check_status(&self) -> JobHandle<Status> {
+        self.queue(Job::CheckStatus)
+    }
+
+    /// Token to cancel the running job, or the next one while idle, see `Printer::cancel_token`.
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    fn queue<R>(&self, job: impl FnOnce(Completer<R>) -> Job) -> JobHandle<R> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                result: None,
+                waker: None,
+            }),
+            done: Condvar::new(),
+        });
+        // 送信に失敗するとCompleterがドロップされWorkerStoppedになる
+        let _ = self.jobs.send(job(Completer {
+            shared: Some(shared.clone()),
+        }));
+        JobHandle { shared }
+    }
+}
+
+struct State<R> {
+    result: Option<Result<R, Error>>,
+    waker: Option<Waker>,
+}
+
+struct Shared<R> {
+    state: Mutex<State<R>>,
+    done: Condvar,
+}
+
+/// Result side of a job, fails the job with `Error::WorkerStopped` when dropped unanswered.
+struct Completer<R> {
+    shared: Option<Arc<Shared<R>>>,
+}
+
+impl<R> Completer<R> {
+    fn complete(mut self, result: Result<R, Error>) {
+        if let Some(shared) = self.shared.take() {
+            let mut state = shared.state.lock().unwrap();
+            state.result = Some(result);
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+            shared.done.notify_all();
+        }
+    }
+}
+
+impl<R> Drop for Completer<R> {
+    fn drop(&mut self) {
+        if self.shared.is_some() {
+            Completer {
+                shared: self.shared.take(),
+            }
+            .complete(Err(Error::WorkerStopped));
+        }
+    }
+}
+
+/// Result of a job submitted to a `SharedPrinter`.
+///
+/// Wait for it with `join`, or `.await` it from async code.
+pub struct JobHandle<R> {
+    shared: Arc<Shared<R>>,
+}
+
+impl<R> JobHandle<R> {
+    /// Block until the job is done.
+    pub fn join(self) -> Result<R, Error> {
+        let mut state = self.shared.state.lock().unwrap();
+        loop {
+            if let Some(result) = state.result.take() {
+                return result;
+            }
+            state = self.shared.done.wait(state).unwrap();
+        }
+    }
+
+    /// `true` once the job is done and `join` returns without blocking.
+    pub fn is_finished(&self) -> bool {
+        self.shared.state.lock().unwrap().result.is_some()
+    }
+}
+
+impl<R> Future for JobHandle<R> {
+    type Output = Result<R, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.shared.state.lock().unwrap();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::fixture::{default_printer, DEFAULT_MEDIA};
+
+    #[test]
+    fn test_jobs_are_serialized() {
+        let (device, printer) = default_printer();
+        let printer = SharedPrinter::new(printer);
+
+        let threads: Vec<_> = (0..4u8)
+            .map(|n| {
+                let printer = printer.clone();
+                thread::spawn(move || {
+                    let pages = vec![vec![vec![n; 90]; 5]; 2];
+                    printer.submit(pages).join()
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap().unwrap();
+        }
+
+        // 同じジョブのページが連続して印刷されている
+        let pages = device.pages();
+        assert_eq!(pages.len(), 8);
+        for job in pages.chunks(2) {
+            assert_eq!(job[0], job[1]);
+        }
+
+        let status = printer.check_status();
+        assert!(status.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_failed_job_does_not_block_queue() {
+        let (device, printer) = default_printer();
+        let printer = SharedPrinter::new(printer);
+
+        device.set_media(None);
+        let failed = printer.submit(vec![vec![vec![0xFF; 90]; 5]]);
+        assert!(matches!(failed.join(), Err(Error::NoMediaInstalled)));
+
+        device.set_media(Some(DEFAULT_MEDIA));
+        printer.submit(vec![vec![vec![0xFF; 90]; 5]]).join().unwrap();
+        assert_eq!(device.pages().len(), 1);
+    }
+
+    #[test]
+    fn test_cancel_token() {
+        let (device, printer) = default_printer();
+        let printer = SharedPrinter::new(printer);
+
+        // 待機中の取り消しは次のジョブに効く
+        printer.cancel_token().cancel();
+        let cancelled = printer.submit(vec![vec![vec![0xFF; 90]; 5]]);
+        assert!(matches!(cancelled.join(), Err(Error::Cancelled { .. })));
+
+        printer.submit(vec![vec![vec![0xFF; 90]; 5]]).join().unwrap();
+        assert_eq!(device.pages().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_await_job() {
+        let (_, printer) = default_printer();
+        let printer = SharedPrinter::new(printer);
+
+        printer.submit(vec![vec![vec![0xFF; 90]; 5]]).await.unwrap();
+        let status = printer.check_status().await.unwrap();
+        assert_eq!(status.media(), Some(DEFAULT_MEDIA));
+    }
+}
@@ -0,0 +1,439 @@
+//! Software stand-in for a Brother QL printer.
+//!
+//! `SimulatedPrinter` implements `Transport`, so a `Printer` can be driven
+//! end-to-end without hardware. It parses the raster stream the driver emits,
+//! keeps the phase the way the device does, answers with 32-byte status
+//! replies and renders every printed page to a `Matrix`.
+
+use log::{debug, warn};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::{
+    decoder::{RasterCommand, RasterDecoder},
+    error::{Error, PrinterErrors},
+    media::{ContinuousType, Media},
+    model::Model,
+    printer::{Config, Printer},
+    status::Phase,
+    transport::{Transport, STATUS_SIZE},
+    Matrix,
+};
+
+// Status type codes (byte 18)
+const STATUS_REPLY: u8 = 0x00;
+const STATUS_COMPLETED: u8 = 0x01;
+const STATUS_ERROR: u8 = 0x02;
+const STATUS_NOTIFICATION: u8 = 0x05;
+const STATUS_PHASE_CHANGE: u8 = 0x06;
+
+// Notification codes (byte 22)
+const COOLING_STARTED: u8 = 0x03;
+const COOLING_FINISHED: u8 = 0x04;
+
+/// Printer simulator usable as a `Transport`.
+///
+/// Clones share the same device state, so a test can hand one clone to a
+/// `Printer` and inspect the received pages through another.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{Config, ContinuousType, Media, Model, Printer, SimulatedPrinter};
+/// let media = Media::Continuous(ContinuousType::Continuous62);
+/// let device = SimulatedPrinter::new(Model::QL820NWB, Some(media));
+///
+/// let config = Config::new(Model::QL820NWB, "serial".to_string(), media);
+/// let printer = Printer::with_transport(device.clone(), config);
+/// printer.print(vec![vec![vec![0xFF; 90]; 10]].into_iter())?;
+///
+/// assert_eq!(device.pages().len(), 1);
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct SimulatedPrinter {
+    state: Arc<Mutex<State>>,
+}
+
+impl SimulatedPrinter {
+    /// Create a simulated printer of the given model with `media` installed.
+    pub fn new(model: Model, media: Option<Media>) -> Self {
+        SimulatedPrinter {
+            state: Arc::new(Mutex::new(State::new(model, media))),
+        }
+    }
+
+    /// Create a simulated printer of `model` loaded with `media`, and a
+    /// `Printer` with the default `Config` driving it.
+    ///
+    /// # Returns
+    /// * `(SimulatedPrinter, Printer)` - The device, to inspect it, and the printer sharing its state
+    ///
+    /// # Example
+    /// ```rust
+    /// # use ql_label::{ContinuousType, Media, Model, SimulatedPrinter};
+    /// let media = Media::Continuous(ContinuousType::Continuous62);
+    /// let (device, printer) = SimulatedPrinter::connect(Model::QL820NWB, media);
+    ///
+    /// assert_eq!(printer.check_status()?.media(), Some(media));
+    /// assert!(device.pages().is_empty());
+    /// # Ok::<(), ql_label::Error>(())
+    /// ```
+    pub fn connect(model: Model, media: Media) -> (Self, Printer<Self>) {
+        let device = SimulatedPrinter::new(model, Some(media));
+        let config = Config::new(model, "simulated".to_string(), media);
+        (device.clone(), Printer::with_transport(device, config))
+    }
+
+    /// Pages printed so far, one `Matrix` per page.
+    ///
+    /// Rows are stored in the order they were received, so a two-color page
+    /// alternates between black and red rows like `TwoColorMatrix::to_alternating_matrix`.
+    pub fn pages(&self) -> Vec<Matrix> {
+        self.state.lock().unwrap().pages.clone()
+    }
+
+    /// Remove and return the pages printed so far.
+    pub fn take_pages(&self) -> Vec<Matrix> {
+        std::mem::take(&mut self.state.lock().unwrap().pages)
+    }
+
+    /// Swap the installed media, `None` removes it.
+    pub fn set_media(&self, media: Option<Media>) {
+        self.state.lock().unwrap().media = media;
+    }
+
+    /// Raise `errors`, like opening the cover or running out of media.
+    ///
+    /// Pages sent while an error is raised are not printed, the device answers
+    /// them with an error status instead. Status replies report the error
+    /// until `clear_errors` is called.
+    pub fn raise_errors(&self, errors: PrinterErrors) {
+        self.state.lock().unwrap().errors |= errors;
+    }
+
+    /// Clear every raised error, as the operator fixing the printer would.
+    pub fn clear_errors(&self) {
+        self.state.lock().unwrap().errors = PrinterErrors::empty();
+    }
+
+    /// Let the print head cool down while printing the next page.
+    ///
+    /// The page is printed after a cooling started notification, `statuses`
+    /// phase change replies and a cooling finished notification.
+    pub fn cool_down_next_page(&self, statuses: usize) {
+        self.state.lock().unwrap().cooling = Some(statuses);
+    }
+
+    /// Current phase of the simulated device.
+    pub fn phase(&self) -> Phase {
+        self.state.lock().unwrap().phase
+    }
+
+    /// Raster count announced by the last `ESC i z` command.
+    pub fn raster_count(&self) -> u32 {
+        self.state.lock().unwrap().raster_count
+    }
+}
+
+impl Transport for SimulatedPrinter {
+    fn write(&self, buf: &[u8]) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.decoder.push(buf);
+        state.process();
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8; STATUS_SIZE], _timeout: Duration) -> Result<usize, Error> {
+        let mut state = self.state.lock().unwrap();
+        match state.replies.pop_front() {
+            Some((reply, phase)) => {
+                // The device leaves a phase once the host has been told about it
+                state.phase = phase;
+                *buf = reply;
+                Ok(STATUS_SIZE)
+            }
+            None => Ok(0),
+        }
+    }
+
+    fn reset(&self) -> Result<(), Error> {
+        let mut state = self.state.lock().unwrap();
+        state.decoder = RasterDecoder::new();
+        state.replies.clear();
+        state.initialize();
+        Ok(())
+    }
+}
+
+struct State {
+    model: Model,
+    media: Option<Media>,
+    decoder: RasterDecoder,
+    replies: VecDeque<([u8; STATUS_SIZE], Phase)>,
+    phase: Phase,
+    auto_status: bool,
+    various_mode: u8,
+    raster_count: u32,
+    rows: Matrix,
+    pages: Vec<Matrix>,
+    errors: PrinterErrors,
+    cooling: Option<usize>,
+}
+
+impl State {
+    fn new(model: Model, media: Option<Media>) -> Self {
+        State {
+            model,
+            media,
+            decoder: RasterDecoder::new(),
+            replies: VecDeque::new(),
+            phase: Phase::Receiving,
+            auto_status: true,
+            various_mode: 0,
+            raster_count: 0,
+            rows: Matrix::new(),
+            pages: Vec::new(),
+            errors: PrinterErrors::empty(),
+            cooling: None,
+        }
+    }
+
+    fn initialize(&mut self) {
+        self.phase = Phase::Receiving;
+        self.auto_status = true;
+        self.various_mode = 0;
+        self.rows.clear();
+    }
+
+    fn process(&mut self) {
+        loop {
+            match self.decoder.next_command() {
+                Ok(Some(command)) => self.apply(command),
+                Ok(None) => break,
+                Err(err) => {
+                    // The device cannot find the next command either
+                    warn!("Simulator discarded {} bytes, {}", self.decoder.pending(), err);
+                    self.decoder = RasterDecoder::new();
+                    break;
+                }
+            }
+        }
+    }
+
+    fn apply(&mut self, command: RasterCommand) {
+        debug!("Simulator received {:?}", command);
+        match command {
+            RasterCommand::Initialize => self.initialize(),
+            RasterCommand::StatusRequest => {
+                let reply = self.status(STATUS_REPLY, self.phase);
+                self.replies.push_back((reply, self.phase));
+            }
+            RasterCommand::SwitchMode(mode) => {
+                if mode != 0x01 {
+                    warn!("Simulator only supports raster mode, got {:#04x}", mode);
+                }
+            }
+            RasterCommand::AutoStatus(enabled) => self.auto_status = enabled,
+            RasterCommand::PrintInformation(info) => self.raster_count = info.raster_count,
+            RasterCommand::VariousMode { auto_cut } => {
+                self.various_mode = if auto_cut { 0b0100_0000 } else { 0 };
+            }
+            // Raster lines arrive already expanded by the decoder
+            RasterCommand::Raster(data) | RasterCommand::ColorRaster { data, .. } => {
+                self.rows.push(data);
+            }
+            RasterCommand::ZeroRaster => {
+                let width = (self.model.pins() / 8) as usize;
+                self.rows.push(vec![0x00; width]);
+            }
+            RasterCommand::Print | RasterCommand::PrintAndEject => self.print(),
+            RasterCommand::Unknown(code) => {
+                // The device skips bytes it does not understand
+                warn!("Simulator ignored unknown command {:#04x}", code);
+            }
+            RasterCommand::Invalidate(_)
+            | RasterCommand::Margin(_)
+            | RasterCommand::CutEach(_)
+            | RasterCommand::ExpandedMode { .. }
+            | RasterCommand::Compression(_) => {}
+        }
+    }
+
+    fn print(&mut self) {
+        let page = std::mem::take(&mut self.rows);
+        if !self.errors.is_empty() {
+            debug!("Simulator dropped page, {}", self.errors);
+            let reply = self.status(STATUS_ERROR, Phase::Receiving);
+            self.replies.push_back((reply, Phase::Receiving));
+            return;
+        }
+        debug!("Simulator printed page with {} rows", page.len());
+        self.pages.push(page);
+
+        self.phase = Phase::Printing;
+        if self.auto_status {
+            let reply = self.status(STATUS_PHASE_CHANGE, Phase::Printing);
+            self.replies.push_back((reply, Phase::Printing));
+
+            if let Some(statuses) = self.cooling.take() {
+                self.notify(COOLING_STARTED);
+                for _ in 0..statuses {
+                    let reply = self.status(STATUS_PHASE_CHANGE, Phase::Printing);
+                    self.replies.push_back((reply, Phase::Printing));
+                }
+                self.notify(COOLING_FINISHED);
+            }
+
+            let sequence = [
+                (STATUS_COMPLETED, Phase::Printing),
+                (STATUS_PHASE_CHANGE, Phase::Receiving),
+            ];
+            for (status_type, phase) in sequence {
+                let reply = self.status(status_type, phase);
+                self.replies.push_back((reply, phase));
+            }
+        } else {
+            self.phase = Phase::Receiving;
+        }
+    }
+
+    fn notify(&mut self, notification: u8) {
+        let mut reply = self.status(STATUS_NOTIFICATION, Phase::Printing);
+        reply[22] = notification;
+        self.replies.push_back((reply, Phase::Printing));
+    }
+
+    fn status(&self, status_type: u8, phase: Phase) -> [u8; STATUS_SIZE] {
+        let mut buf = [0u8; STATUS_SIZE];
+        buf[0] = 0x80; // Print head mark
+        buf[1] = 0x20; // Size
+        buf[2] = 0x42; // Brother code
+        buf[3] = 0x34; // Series code
+        buf[4] = self.model.code();
+        buf[5] = 0x30; // Country code
+        let [error_1, error_2] = self.errors.bits().to_le_bytes();
+        buf[8] = error_1;
+        buf[9] = error_2;
+        if let Some(media) = self.media {
+            let spec = media.spec();
+            buf[10] = spec.width_mm();
+            buf[17] = spec.length_mm();
+            match media {
+                Media::Continuous(t) => {
+                    buf[11] = 0x0A;
+                    buf[25] = if t == ContinuousType::Continuous62Red {
+                        0x81
+                    } else {
+                        0x01
+                    };
+                }
+                Media::DieCut(_) => {
+                    buf[11] = 0x0B;
+                    buf[25] = 0x01;
+                }
+            }
+        }
+        buf[15] = self.various_mode;
+        buf[18] = status_type;
+        buf[19] = match phase {
+            Phase::Receiving => 0x00,
+            Phase::Printing => 0x01,
+            Phase::Waiting(_) => 0x02,
+        };
+        buf
+    }
+}
+
+/// Setup shared by the end-to-end tests of every module.
+#[cfg(test)]
+pub(crate) mod fixture {
+    use super::*;
+
+    /// Media loaded by `default_printer`.
+    pub(crate) const DEFAULT_MEDIA: Media = Media::Continuous(ContinuousType::Continuous62);
+
+    /// Simulated `model` loaded with `media`, driven by a `Printer` whose
+    /// default `Config` is adjusted by `configure`.
+    pub(crate) fn printer(
+        model: Model,
+        media: Media,
+        configure: impl FnOnce(Config) -> Config,
+    ) -> (SimulatedPrinter, Printer<SimulatedPrinter>) {
+        let device = SimulatedPrinter::new(model, Some(media));
+        let config = configure(Config::new(model, "serial".to_string(), media));
+        (device.clone(), Printer::with_transport(device, config))
+    }
+
+    /// `printer` for a QL-820NWB with 62mm continuous tape and the default `Config`.
+    pub(crate) fn default_printer() -> (SimulatedPrinter, Printer<SimulatedPrinter>) {
+        printer(Model::QL820NWB, DEFAULT_MEDIA, |config| config)
+    }
+
+    /// Page of `rows` lines with every pixel value, for the normal width.
+    pub(crate) fn pattern(rows: usize) -> Matrix {
+        (0..rows)
+            .map(|y| (0..90).map(|x| ((x * 7 + y * 3) % 5 * 51) as u8).collect())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixture::{default_printer, pattern, printer};
+    use super::*;
+    use crate::TwoColorMatrix;
+
+    #[test]
+    fn test_print_multiple_pages() {
+        let (device, printer) = default_printer();
+
+        let pages = vec![pattern(20), pattern(35), pattern(1)];
+        printer.print(pages.clone().into_iter()).unwrap();
+
+        assert_eq!(device.pages(), pages);
+        assert_eq!(device.phase(), Phase::Receiving);
+    }
+
+    #[test]
+    fn test_print_compressed() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (device, printer) = printer(Model::QL820NWB, media, |config| config.compress(true));
+
+        let mut page = pattern(10);
+        page.push(vec![0x00; 90]);
+        printer.print(vec![page.clone()].into_iter()).unwrap();
+
+        assert_eq!(device.pages(), vec![page]);
+        assert_eq!(device.raster_count(), 11);
+    }
+
+    #[test]
+    fn test_print_two_color() {
+        let media = Media::Continuous(ContinuousType::Continuous62Red);
+        let (device, printer) = printer(Model::QL820NWB, media, |config| config.two_colors(true));
+
+        let two_color = TwoColorMatrix::new(pattern(4), vec![vec![0x0F; 90]; 4]).unwrap();
+        printer
+            .print_two_color(vec![two_color.clone()].into_iter())
+            .unwrap();
+
+        assert_eq!(device.pages(), vec![two_color.to_alternating_matrix()]);
+        assert_eq!(device.raster_count(), 4);
+    }
+
+    #[test]
+    fn test_media_mismatch() {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let (device, printer) = printer(Model::QL800, media, |config| config);
+        device.set_media(Some(Media::Continuous(ContinuousType::Continuous29)));
+
+        let result = printer.print(vec![pattern(2)].into_iter());
+        assert!(matches!(result, Err(Error::MediaMismatch { .. })));
+
+        device.set_media(None);
+        let result = printer.print(vec![pattern(2)].into_iter());
+        assert!(matches!(result, Err(Error::NoMediaInstalled)));
+        assert!(device.pages().is_empty());
+    }
+}
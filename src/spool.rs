@@ -0,0 +1,861 @@
+//! Persistent print queue.
+//!
+//! `Spooler` accepts jobs, a `Config` plus its pages, and keeps them in a
+//! directory until they are printed. Every job is stored as two files:
+//!
+//! * `<id>.pages` - the raster pages, written once when the job is accepted
+//! * `<id>.job` - a small text header with the configuration, the priority,
+//!   the state and the number of pages printed so far
+//!
+//! `next_id` holds the identifier of the next job, so identifiers are never
+//! reused, not even those of jobs already printed.
+//!
+//! All files are replaced atomically, and the header is updated after every
+//! printed page, so after a crash the queue is restored by `Spooler::open`
+//! and an interrupted job resumes from its first unprinted page.
+
+use log::{debug, error, info, warn};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::{
+    cancel::CancelToken,
+    error::Error,
+    event::JobEvent,
+    media::{ContinuousType, Media},
+    model::Model,
+    printer::{Config, Printer, RecoveryPolicy},
+    transport::Transport,
+    Matrix,
+};
+
+const HEADER_MAGIC: &str = "ql-label spool 1";
+const PAGES_MAGIC: &[u8; 4] = b"QLPG";
+const NEXT_ID_FILE: &str = "next_id";
+
+/// Identifier of a spooled job, assigned in submission order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct JobId(pub u64);
+
+impl fmt::Display for JobId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// State of a spooled job.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobState {
+    /// Waiting to be printed
+    Queued,
+    /// Being printed right now
+    Printing,
+    /// Held back until `Spooler::resume`
+    Paused,
+    /// Stopped by an error other than the printer being offline, with its message
+    Failed(String),
+}
+
+/// Snapshot of a spooled job, see `Spooler::jobs`.
+#[derive(Debug, Clone)]
+pub struct SpoolJob {
+    pub id: JobId,
+    /// Jobs with a higher priority are printed first
+    pub priority: i32,
+    pub state: JobState,
+    /// Number of pages of the job
+    pub pages: usize,
+    /// Number of pages already printed
+    pub printed: usize,
+    pub config: Config,
+}
+
+/// On-disk print queue.
+///
+/// Clones share the same queue, so jobs can be submitted from any thread
+/// while another one runs `Spooler::run`.
+///
+/// # Example
+/// ```rust,no_run
+/// # use ql_label::{Config, ContinuousType, Media, Model, Printer, Spooler};
+/// # use std::time::Duration;
+/// let spooler = Spooler::open("/var/spool/ql-label")?;
+///
+/// let config = Config::new(Model::QL820NWB, "E8N117P02180".to_string(),
+///                         Media::Continuous(ContinuousType::Continuous62));
+/// let id = spooler.submit(config, vec![vec![vec![0xFF; 90]; 300]])?;
+/// println!("queued {}", id);
+///
+/// // Print forever, retrying jobs of disconnected printers every 10 seconds
+/// spooler.run(|config| Printer::new(config.clone()), Duration::from_secs(10));
+/// # Ok::<(), ql_label::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct Spooler {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    dir: PathBuf,
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+struct Queue {
+    jobs: BTreeMap<JobId, Entry>,
+    next_id: u64,
+    // 印刷中のジョブとそのキャンセル用トークン
+    current: Option<(JobId, CancelToken)>,
+    shutdown: bool,
+}
+
+struct Entry {
+    priority: i32,
+    state: JobState,
+    pages: usize,
+    printed: usize,
+    config: Config,
+}
+
+impl Spooler {
+    /// Open the queue stored in `dir`, creating the directory if needed.
+    ///
+    /// Jobs which were being printed when the process stopped are queued
+    /// again. Files which cannot be read are skipped with a warning, pages
+    /// without a header and unfinished temporary files are removed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut jobs = BTreeMap::new();
+        let mut leftovers = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            match path.extension().and_then(|extension| extension.to_str()) {
+                Some("job") => match load_header(&path) {
+                    Ok((id, entry)) => {
+                        jobs.insert(id, entry);
+                    }
+                    Err(err) => warn!("Skipping spool file {}: {}", path.display(), err),
+                },
+                // ヘッダーの保存前に終了した
+                Some("pages") if !path.with_extension("job").exists() => leftovers.push(path),
+                // 置き換え前に終了した
+                Some("tmp") => leftovers.push(path),
+                _ => {}
+            }
+        }
+        for path in leftovers {
+            info!("Removing incomplete spool file {}", path.display());
+            fs::remove_file(&path)?;
+        }
+        let saved_id = match fs::read_to_string(dir.join(NEXT_ID_FILE)) {
+            Ok(text) => text.trim().parse().map_err(|_| {
+                Error::SpoolCorrupted(format!("invalid {} in {}", NEXT_ID_FILE, dir.display()))
+            })?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 1,
+            Err(err) => return Err(err.into()),
+        };
+        // 保存前に終了した場合に備え、残っているジョブより後の番号にする
+        let next_id = jobs.keys().next_back().map_or(1, |id| id.0 + 1).max(saved_id);
+        info!("Opened spool {} with {} jobs", dir.display(), jobs.len());
+
+        Ok(Spooler {
+            inner: Arc::new(Inner {
+                dir,
+                queue: Mutex::new(Queue {
+                    jobs,
+                    next_id,
+                    current: None,
+                    shutdown: false,
+                }),
+                changed: Condvar::new(),
+            }),
+        })
+    }
+
+    /// Store a job in the queue.
+    ///
+    /// The job is on disk when this returns, so it is not lost even if the
+    /// process stops before it is printed.
+    ///
+    /// # Returns
+    /// * `Ok(JobId)` - The job is queued
+    /// * `Err(Error::InvalidConfig)` - The configuration is not supported by the model
+    /// * `Err(Error::IoError)` - The job could not be written
+    pub fn submit(&self, config: Config, pages: impl IntoIterator<Item = Matrix>) -> Result<JobId, Error> {
+        config.validate()?;
+        let pages: Vec<Matrix> = pages.into_iter().collect();
+
+        let id = {
+            let mut queue = self.inner.queue.lock().unwrap();
+            let id = JobId(queue.next_id);
+            // 印刷済みのジョブの番号を再利用しないよう、先に保存する
+            write_atomic(&self.inner.dir.join(NEXT_ID_FILE), (id.0 + 1).to_string().as_bytes())?;
+            queue.next_id += 1;
+            id
+        };
+        // ページを先に書き、ヘッダーがあれば必ずページもあるようにする
+        write_atomic(&self.inner.path(id, "pages"), &encode_pages(&pages))?;
+
+        let entry = Entry {
+            priority: 0,
+            state: JobState::Queued,
+            pages: pages.len(),
+            printed: 0,
+            config,
+        };
+        let mut queue = self.inner.queue.lock().unwrap();
+        self.inner.save(id, &entry)?;
+        queue.jobs.insert(id, entry);
+        debug!("Spooled job {} with {} pages", id, pages.len());
+        self.inner.changed.notify_all();
+        Ok(id)
+    }
+
+    /// Every job in the queue, in the order they will be printed.
+    pub fn jobs(&self) -> Vec<SpoolJob> {
+        let queue = self.inner.queue.lock().unwrap();
+        let mut jobs: Vec<SpoolJob> = queue
+            .jobs
+            .iter()
+            .map(|(id, entry)| SpoolJob {
+                id: *id,
+                priority: entry.priority,
+                state: entry.state.clone(),
+                pages: entry.pages,
+                printed: entry.printed,
+                config: entry.config.clone(),
+            })
+            .collect();
+        jobs.sort_by_key(|job| (Reverse(job.priority), job.id));
+        jobs
+    }
+
+    /// Change the priority of a job, higher priorities are printed first.
+    pub fn set_priority(&self, id: JobId, priority: i32) -> Result<(), Error> {
+        self.update(id, |entry| entry.priority = priority)
+    }
+
+    /// Hold a job back until `resume` is called.
+    ///
+    /// A job being printed stops after the current page and resumes from
+    /// the next one.
+    pub fn pause(&self, id: JobId) -> Result<(), Error> {
+        self.update(id, |entry| entry.state = JobState::Paused)?;
+        self.inner.cancel_current(id);
+        Ok(())
+    }
+
+    /// Queue a paused or failed job again.
+    pub fn resume(&self, id: JobId) -> Result<(), Error> {
+        self.update(id, |entry| {
+            if entry.state != JobState::Printing {
+                entry.state = JobState::Queued;
+            }
+        })
+    }
+
+    /// Remove a job from the queue, stopping it if it is being printed.
+    pub fn cancel(&self, id: JobId) -> Result<(), Error> {
+        let mut queue = self.inner.queue.lock().unwrap();
+        queue.jobs.remove(&id).ok_or(Error::JobNotFound(id))?;
+        let printing = matches!(&queue.current, Some((current, _)) if *current == id);
+        drop(queue);
+
+        if printing {
+            // 印刷中のジョブのファイルは印刷側で削除する
+            self.inner.cancel_current(id);
+            Ok(())
+        } else {
+            self.inner.remove_files(id)
+        }
+    }
+
+    /// Print every queued job, in order, and return how many were completed.
+    ///
+    /// `open` connects to the printer of a job. When it, or the job, fails
+    /// with `Error::DeviceOffline` the job stays queued and the later jobs
+    /// for the same printer are kept back as well, so they are printed in
+    /// order once the printer returns. Other errors mark the job as failed.
+    ///
+    /// The observer of the printers returned by `open` is replaced, it is
+    /// used to record the printed pages.
+    pub fn print_pending<T, F>(&self, mut open: F) -> Result<usize, Error>
+    where
+        T: Transport,
+        F: FnMut(&Config) -> Result<Printer<T>, Error>,
+    {
+        let mut offline: HashSet<String> = HashSet::new();
+        let mut completed = 0;
+
+        while let Some((id, config)) = self.next_job(&offline) {
+            let mut printer = match open(&config) {
+                Ok(printer) => printer,
+                Err(err) if is_offline(&err) => {
+                    info!("Printer {} is offline, keeping job {} queued", config.serial, id);
+                    offline.insert(config.serial.clone());
+                    continue;
+                }
+                Err(err) => {
+                    self.finish(id, Err(err))?;
+                    continue;
+                }
+            };
+
+            let printed = {
+                let mut queue = self.inner.queue.lock().unwrap();
+                let entry = match queue.jobs.get_mut(&id) {
+                    Some(entry) if entry.state == JobState::Queued => entry,
+                    // 接続中に一時停止・キャンセルされた
+                    _ => continue,
+                };
+                entry.state = JobState::Printing;
+                let printed = entry.printed;
+                queue.current = Some((id, printer.cancel_token()));
+                printed
+            };
+
+            let inner = self.inner.clone();
+            printer.set_observer(move |event: &JobEvent| {
+                if let JobEvent::PagePrinted { page } = event {
+                    inner.page_printed(id, printed + page + 1);
+                }
+            });
+
+            let result = self
+                .inner
+                .load_pages(id)
+                .and_then(|pages| printer.print(pages.into_iter().skip(printed)));
+            match result {
+                Err(err) if is_offline(&err) => {
+                    info!("Printer {} went offline during job {}", config.serial, id);
+                    offline.insert(config.serial.clone());
+                    self.requeue(id)?;
+                }
+                result => {
+                    if result.is_ok() {
+                        completed += 1;
+                    }
+                    self.finish(id, result)?;
+                }
+            }
+        }
+        Ok(completed)
+    }
+
+    /// Print jobs as they arrive until `shutdown` is called.
+    ///
+    /// Jobs kept back because their printer is offline are retried every
+    /// `retry_interval`. Errors storing the state of a job are logged, the
+    /// remaining jobs are retried after `retry_interval` as well.
+    pub fn run<T, F>(&self, mut open: F, retry_interval: Duration)
+    where
+        T: Transport,
+        F: FnMut(&Config) -> Result<Printer<T>, Error>,
+    {
+        loop {
+            if let Err(err) = self.print_pending(&mut open) {
+                error!("Failed to update spool {}: {}", self.inner.dir.display(), err);
+            }
+
+            let queue = self.inner.queue.lock().unwrap();
+            if queue.shutdown {
+                return;
+            }
+            let (queue, _) = self
+                .inner
+                .changed
+                .wait_timeout(queue, retry_interval)
+                .unwrap();
+            if queue.shutdown {
+                return;
+            }
+        }
+    }
+
+    /// Make `run` return once the job being printed is done.
+    pub fn shutdown(&self) {
+        self.inner.queue.lock().unwrap().shutdown = true;
+        self.inner.changed.notify_all();
+    }
+
+    fn update(&self, id: JobId, change: impl FnOnce(&mut Entry)) -> Result<(), Error> {
+        let mut queue = self.inner.queue.lock().unwrap();
+        let entry = queue.jobs.get_mut(&id).ok_or(Error::JobNotFound(id))?;
+        change(entry);
+        self.inner.save(id, entry)?;
+        self.inner.changed.notify_all();
+        Ok(())
+    }
+
+    fn next_job(&self, offline: &HashSet<String>) -> Option<(JobId, Config)> {
+        let queue = self.inner.queue.lock().unwrap();
+        queue
+            .jobs
+            .iter()
+            .filter(|(_, entry)| !offline.contains(&entry.config.serial))
+            .filter(|(_, entry)| entry.state == JobState::Queued)
+            .min_by_key(|(id, entry)| (Reverse(entry.priority), **id))
+            .map(|(id, entry)| (*id, entry.config.clone()))
+    }
+
+    fn requeue(&self, id: JobId) -> Result<(), Error> {
+        let mut queue = self.inner.queue.lock().unwrap();
+        queue.current = None;
+        if let Some(entry) = queue.jobs.get_mut(&id) {
+            if entry.state == JobState::Printing {
+                entry.state = JobState::Queued;
+            }
+            return self.inner.save(id, entry);
+        }
+        drop(queue);
+        self.inner.remove_files(id)
+    }
+
+    fn finish(&self, id: JobId, result: Result<(), Error>) -> Result<(), Error> {
+        let mut queue = self.inner.queue.lock().unwrap();
+        queue.current = None;
+        match result {
+            Ok(()) => {
+                info!("Spooled job {} printed", id);
+                queue.jobs.remove(&id);
+            }
+            Err(Error::Cancelled { completed }) => {
+                if let Some(entry) = queue.jobs.get_mut(&id) {
+                    // 一時停止された
+                    debug!("Spooled job {} paused after {} pages", id, completed);
+                    if entry.state == JobState::Printing {
+                        entry.state = JobState::Queued;
+                    }
+                    return self.inner.save(id, entry);
+                }
+                info!("Spooled job {} cancelled", id);
+            }
+            Err(err) => {
+                warn!("Spooled job {} failed: {}", id, err);
+                if let Some(entry) = queue.jobs.get_mut(&id) {
+                    entry.state = JobState::Failed(err.to_string());
+                    return self.inner.save(id, entry);
+                }
+            }
+        }
+        drop(queue);
+        self.inner.remove_files(id)
+    }
+}
+
+impl Inner {
+    fn path(&self, id: JobId, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id.0, extension))
+    }
+
+    fn save(&self, id: JobId, entry: &Entry) -> Result<(), Error> {
+        write_atomic(&self.path(id, "job"), encode_header(id, entry).as_bytes())
+    }
+
+    fn load_pages(&self, id: JobId) -> Result<Vec<Matrix>, Error> {
+        decode_pages(&fs::read(self.path(id, "pages"))?)
+    }
+
+    fn remove_files(&self, id: JobId) -> Result<(), Error> {
+        for extension in ["job", "pages"] {
+            match fs::remove_file(self.path(id, extension)) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn cancel_current(&self, id: JobId) {
+        let queue = self.queue.lock().unwrap();
+        if let Some((current, token)) = &queue.current {
+            if *current == id {
+                token.cancel();
+            }
+        }
+    }
+
+    fn page_printed(&self, id: JobId, printed: usize) {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(entry) = queue.jobs.get_mut(&id) {
+            entry.printed = printed;
+            if let Err(err) = self.save(id, entry) {
+                warn!("Failed to record progress of job {}: {}", id, err);
+            }
+        }
+    }
+}
+
+fn is_offline(err: &Error) -> bool {
+    match err {
+        Error::DeviceOffline | Error::UsbError(rusb::Error::NoDevice) => true,
+        Error::JobInterrupted { source, .. } => is_offline(source),
+        _ => false,
+    }
+}
+
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), Error> {
+    // <id>.jobと<id>.pagesで一時ファイルが重ならないよう、元の名前に付け足す
+    let mut tmp = path.as_os_str().to_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut file = File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    // 名前の変更自体を永続化する
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn encode_header(id: JobId, entry: &Entry) -> String {
+    let config = &entry.config;
+    let (state, error) = match &entry.state {
+        // 印刷中にプロセスが終了した場合は再開する
+        JobState::Queued | JobState::Printing => ("queued", None),
+        JobState::Paused => ("paused", None),
+        JobState::Failed(message) => ("failed", Some(message.replace('\n', " "))),
+    };
+
+    let mut header = String::new();
+    let mut field = |key: &str, value: &dyn fmt::Display| header.push_str(&format!("{}={}\n", key, value));
+    field("id", &id.0);
+    field("priority", &entry.priority);
+    field("state", &state);
+    if let Some(error) = error {
+        field("error", &error);
+    }
+    field("pages", &entry.pages);
+    field("printed", &entry.printed);
+    field("model", &config.model);
+    field("serial", &config.serial);
+    // 名前ではなく、変わらない識別子で保存する
+    field("media", &config.media.id());
+    field("red_media", &is_red(config.media));
+    field("auto_cut", &config.auto_cut_size().unwrap_or(0));
+    field("cut_at_end", &config.cut_at_end);
+    field("two_colors", &config.two_colors);
+    field("high_resolution", &config.high_resolution);
+    field("feed", &config.feed);
+    field("compress", &config.compress);
+    match config.recovery {
+        Some(policy) => field(
+            "recovery",
+            &format!("{},{}", policy.deadline.as_millis(), policy.poll_interval.as_millis()),
+        ),
+        None => field("recovery", &"none"),
+    }
+    field("completion_attempts", &config.completion_attempts);
+    format!("{}\n{}", HEADER_MAGIC, header)
+}
+
+fn load_header(path: &Path) -> Result<(JobId, Entry), Error> {
+    let text = fs::read_to_string(path)?;
+    let mut lines = text.lines();
+    if lines.next() != Some(HEADER_MAGIC) {
+        return Err(Error::SpoolCorrupted(format!("{} is not a spool file", path.display())));
+    }
+    let fields: BTreeMap<&str, &str> = lines.filter_map(|line| line.split_once('=')).collect();
+
+    let corrupted = |key: &str| Error::SpoolCorrupted(format!("invalid {} in {}", key, path.display()));
+    let get = |key: &str| fields.get(key).copied().ok_or_else(|| corrupted(key));
+    let parse = |key: &str| -> Result<u64, Error> { get(key)?.parse().map_err(|_| corrupted(key)) };
+    let flag = |key: &str| -> Result<bool, Error> { get(key)?.parse().map_err(|_| corrupted(key)) };
+
+    let id = JobId(parse("id")?);
+    let model: Model = get("model")?.parse().map_err(|_| corrupted("model"))?;
+    let (media_id, red) = (parse("media")?, flag("red_media")?);
+    let media = Media::ALL
+        .iter()
+        .copied()
+        .find(|media| u64::from(media.id()) == media_id && is_red(*media) == red)
+        .ok_or_else(|| corrupted("media"))?;
+
+    let mut config = Config::new(model, get("serial")?.to_string(), media)
+        .cut_at_end(flag("cut_at_end")?)
+        .two_colors(flag("two_colors")?)
+        .high_resolution(flag("high_resolution")?)
+        .set_feed_in_dots(parse("feed")? as u16)
+        .compress(flag("compress")?);
+    config = match parse("auto_cut")? {
+        0 => config.disable_auto_cut(),
+        size => config.enable_auto_cut(size as u8),
+    };
+    if let Some((deadline, poll_interval)) = get("recovery")?.split_once(',') {
+        let millis = |value: &str| value.parse().map(Duration::from_millis).map_err(|_| corrupted("recovery"));
+        config = config.recover(RecoveryPolicy::new(millis(deadline)?).poll_interval(millis(poll_interval)?));
+    }
+    // 古いスプールファイルには含まれない
+    if fields.contains_key("completion_attempts") {
+        config = config.completion_attempts(parse("completion_attempts")? as u32);
+    }
+
+    let state = match get("state")? {
+        "queued" => JobState::Queued,
+        "paused" => JobState::Paused,
+        "failed" => JobState::Failed(fields.get("error").unwrap_or(&"").to_string()),
+        _ => return Err(corrupted("state")),
+    };
+    let priority = get("priority")?.parse().map_err(|_| corrupted("priority"))?;
+
+    let entry = Entry {
+        priority,
+        state,
+        pages: parse("pages")? as usize,
+        printed: parse("printed")? as usize,
+        config,
+    };
+    Ok((id, entry))
+}
+
+fn is_red(media: Media) -> bool {
+    media == Media::Continuous(ContinuousType::Continuous62Red)
+}
+
+// ページ数、各ページの行数、各行のバイト数をリトルエンディアンのu32で書く
+fn encode_pages(pages: &[Matrix]) -> Vec<u8> {
+    let mut buf = PAGES_MAGIC.to_vec();
+    buf.extend_from_slice(&(pages.len() as u32).to_le_bytes());
+    for page in pages {
+        buf.extend_from_slice(&(page.len() as u32).to_le_bytes());
+        for row in page {
+            buf.extend_from_slice(&(row.len() as u32).to_le_bytes());
+            buf.extend_from_slice(row);
+        }
+    }
+    buf
+}
+
+fn decode_pages(mut data: &[u8]) -> Result<Vec<Matrix>, Error> {
+    if take(&mut data, PAGES_MAGIC.len())? != PAGES_MAGIC {
+        return Err(Error::SpoolCorrupted("invalid pages file".to_string()));
+    }
+
+    // ページと行はそれぞれ4バイト以上あるので、残りの大きさで個数を制限する
+    let count = take_count(&mut data)?;
+    let mut pages = Vec::with_capacity(count);
+    for _ in 0..count {
+        let rows = take_count(&mut data)?;
+        let mut page = Matrix::with_capacity(rows);
+        for _ in 0..rows {
+            let len = take_u32(&mut data)? as usize;
+            page.push(take(&mut data, len)?.to_vec());
+        }
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if data.len() < len {
+        return Err(Error::SpoolCorrupted("pages file is truncated".to_string()));
+    }
+    let (head, rest) = data.split_at(len);
+    *data = rest;
+    Ok(head)
+}
+
+fn take_u32(data: &mut &[u8]) -> Result<u32, Error> {
+    let bytes = take(data, 4)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+// 続く要素の数、要素ごとに少なくとも長さの4バイトが残っていること
+fn take_count(data: &mut &[u8]) -> Result<usize, Error> {
+    let count = take_u32(data)? as usize;
+    if count > data.len() / 4 {
+        return Err(Error::SpoolCorrupted(format!("{} entries in a truncated pages file", count)));
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PrinterErrors, SimulatedPrinter};
+
+    fn spool_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ql-label-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn config() -> Config {
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        Config::new(Model::QL820NWB, "serial".to_string(), media)
+            .enable_auto_cut(2)
+            .recover(RecoveryPolicy::new(Duration::from_secs(30)))
+            .completion_attempts(250)
+    }
+
+    fn page(value: u8) -> Matrix {
+        vec![vec![value; 90]; 4]
+    }
+
+    #[test]
+    fn test_queue_survives_restart() {
+        let dir = spool_dir("restart");
+        let spooler = Spooler::open(&dir).unwrap();
+        let first = spooler.submit(config(), vec![page(1)]).unwrap();
+        let second = spooler.submit(config(), vec![page(2), page(3)]).unwrap();
+        let paused = spooler.submit(config(), vec![page(4)]).unwrap();
+        spooler.set_priority(second, 5).unwrap();
+        spooler.pause(paused).unwrap();
+        drop(spooler);
+
+        let spooler = Spooler::open(&dir).unwrap();
+        let jobs = spooler.jobs();
+        assert_eq!(jobs.iter().map(|job| job.id).collect::<Vec<_>>(), vec![second, first, paused]);
+        assert_eq!(jobs[2].state, JobState::Paused);
+        assert_eq!(jobs[0].pages, 2);
+        assert_eq!(jobs[0].config.auto_cut_size(), Some(2));
+        assert_eq!(jobs[0].config.recovery, config().recovery);
+        assert_eq!(jobs[0].config.completion_attempts, 250);
+
+        let device = SimulatedPrinter::new(Model::QL820NWB, Some(config().media));
+        let completed = spooler
+            .print_pending(|config| Ok(Printer::with_transport(device.clone(), config.clone())))
+            .unwrap();
+        assert_eq!(completed, 2);
+        assert_eq!(device.pages(), vec![page(2), page(3), page(1)]);
+
+        spooler.cancel(paused).unwrap();
+        assert!(spooler.jobs().is_empty());
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        assert_eq!(files, vec![NEXT_ID_FILE]);
+        drop(spooler);
+
+        // 印刷済みのジョブの番号は再利用しない
+        let spooler = Spooler::open(&dir).unwrap();
+        assert_eq!(spooler.submit(config(), vec![page(5)]).unwrap(), JobId(paused.0 + 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_header_keeps_every_media() {
+        let dir = spool_dir("media");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("1.job");
+        for media in Media::ALL.iter().copied() {
+            let entry = Entry {
+                priority: 0,
+                state: JobState::Queued,
+                pages: 1,
+                printed: 0,
+                config: Config::new(Model::QL820NWB, "serial".to_string(), media),
+            };
+            fs::write(&path, encode_header(JobId(1), &entry)).unwrap();
+            assert_eq!(load_header(&path).unwrap().1.config.media, media);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_incomplete_files_removed() {
+        let dir = spool_dir("incomplete");
+        let spooler = Spooler::open(&dir).unwrap();
+        let id = spooler.submit(config(), vec![page(1)]).unwrap();
+        drop(spooler);
+
+        // ヘッダーを書く前、名前を変える前に終了した
+        fs::write(dir.join("7.pages"), encode_pages(&[page(2)])).unwrap();
+        fs::write(dir.join("8.job.tmp"), b"").unwrap();
+        let spooler = Spooler::open(&dir).unwrap();
+        assert_eq!(spooler.jobs().iter().map(|job| job.id).collect::<Vec<_>>(), vec![id]);
+
+        let mut files: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        files.sort();
+        assert_eq!(files, vec!["1.job", "1.pages", NEXT_ID_FILE]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_pages_rejected() {
+        let pages = encode_pages(&[page(1), page(2)]);
+        assert_eq!(decode_pages(&pages).unwrap(), vec![page(1), page(2)]);
+
+        // 途中で切れたファイル
+        for len in [3, 10, pages.len() - 1] {
+            assert!(matches!(decode_pages(&pages[..len]), Err(Error::SpoolCorrupted(_))), "{}", len);
+        }
+        // 巨大な個数や長さを確保しようとしない
+        let mut huge = PAGES_MAGIC.to_vec();
+        huge.extend_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(decode_pages(&huge), Err(Error::SpoolCorrupted(_))));
+        let mut long_row = PAGES_MAGIC.to_vec();
+        for value in [1u32, 1, u32::MAX] {
+            long_row.extend_from_slice(&value.to_le_bytes());
+        }
+        assert!(matches!(decode_pages(&long_row), Err(Error::SpoolCorrupted(_))));
+    }
+
+    #[test]
+    fn test_offline_printer_keeps_jobs_queued() {
+        let dir = spool_dir("offline");
+        let spooler = Spooler::open(&dir).unwrap();
+        spooler.submit(config(), vec![page(1)]).unwrap();
+        spooler.submit(config(), vec![page(2)]).unwrap();
+
+        let completed = spooler
+            .print_pending(|_| -> Result<Printer<SimulatedPrinter>, Error> { Err(Error::DeviceOffline) })
+            .unwrap();
+        assert_eq!(completed, 0);
+        assert!(spooler.jobs().iter().all(|job| job.state == JobState::Queued));
+
+        let device = SimulatedPrinter::new(Model::QL820NWB, Some(config().media));
+        let completed = spooler
+            .print_pending(|config| Ok(Printer::with_transport(device.clone(), config.clone())))
+            .unwrap();
+        assert_eq!(completed, 2);
+        assert_eq!(device.pages(), vec![page(1), page(2)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_failed_job_resumes_from_first_unprinted_page() {
+        let dir = spool_dir("resume");
+        let spooler = Spooler::open(&dir).unwrap();
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let config = Config::new(Model::QL820NWB, "serial".to_string(), media);
+        let id = spooler.submit(config, vec![page(1), page(2), page(3)]).unwrap();
+
+        // 1ページ目の印刷後に致命的なエラーを起こす
+        let device = SimulatedPrinter::new(Model::QL820NWB, Some(media));
+        let operator = device.clone();
+        let watcher = std::thread::spawn(move || {
+            while operator.pages().is_empty() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            operator.raise_errors(PrinterErrors::CUTTER_JAM);
+        });
+        spooler
+            .print_pending(|config| Ok(Printer::with_transport(device.clone(), config.clone())))
+            .unwrap();
+        watcher.join().unwrap();
+        drop(spooler);
+
+        let spooler = Spooler::open(&dir).unwrap();
+        let job = &spooler.jobs()[0];
+        assert!(matches!(job.state, JobState::Failed(_)));
+        assert_eq!(job.printed, 1);
+
+        device.clear_errors();
+        spooler.resume(id).unwrap();
+        spooler
+            .print_pending(|config| Ok(Printer::with_transport(device.clone(), config.clone())))
+            .unwrap();
+        assert_eq!(device.pages(), vec![page(1), page(2), page(3)]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
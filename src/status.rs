@@ -0,0 +1,364 @@
+//! Printer status reply.
+//!
+//! The printer answers a status request, and reports every phase change
+//! while printing, with a fixed 32-byte reply. `Status` decodes every field
+//! of it and keeps the raw bytes for anything not covered here.
+
+use std::fmt;
+
+use crate::{
+    error::{Error, PrinterErrors},
+    media::Media,
+    model::ReportedModel,
+    transport::STATUS_SIZE,
+};
+
+/// Kind of media reported in byte 11.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum MediaType {
+    NoMedia,
+    Continuous,
+    DieCut,
+    Unknown(u8),
+}
+
+impl MediaType {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => Self::NoMedia,
+            // Document says 0x4A/0x4B but actual values seem to be 0x0A/0x0B
+            0x0A | 0x4A => Self::Continuous,
+            0x0B | 0x4B => Self::DieCut,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+///
+/// Status received from the printer encoded to Rust friendly type.
+///
+/// # Example
+/// ```rust
+/// # use ql_label::{Phase, Status, StatusType};
+/// let mut buf = [0u8; 32];
+/// buf[..6].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x38, 0x30]);
+/// buf[9] = 0x10; // cover open
+/// buf[10] = 62;
+/// buf[11] = 0x0A;
+/// buf[18] = 0x02;
+///
+/// let status = Status::from_buf(buf);
+/// assert_eq!(status.status_type(), StatusType::Error);
+/// assert_eq!(status.phase(), Phase::Receiving);
+/// assert_eq!(status.media_width_mm(), 62);
+/// assert_eq!(status.to_string(), "QL-800 error, receiving, 62mm continuous: cover open");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Status {
+    model: ReportedModel,
+    media: Option<Media>,
+    mode: u8,
+    status_type: StatusType,
+    phase: Phase,
+    notification: Notification,
+    id: u8,
+    raw: [u8; STATUS_SIZE],
+}
+
+impl Status {
+    /// Decode a 32-byte status reply.
+    pub fn from_buf(buf: [u8; STATUS_SIZE]) -> Self {
+        Status {
+            model: ReportedModel::from_code(buf[4]),
+            media: Media::from_buf(buf),
+            mode: buf[15],
+            status_type: StatusType::from_code(buf[18]),
+            phase: Phase::from_buf(buf),
+            notification: Notification::from_code(buf[22]),
+            id: buf[14],
+            raw: buf,
+        }
+    }
+
+    /// The undecoded reply.
+    pub fn raw(&self) -> &[u8; STATUS_SIZE] {
+        &self.raw
+    }
+
+    /// Print head mark (byte 0), always `0x80`.
+    pub fn head_mark(&self) -> u8 {
+        self.raw[0]
+    }
+
+    /// Model code (byte 4).
+    pub fn model_code(&self) -> u8 {
+        self.raw[4]
+    }
+
+    /// Model decoded from the model code, `ReportedModel::Unknown` for codes
+    /// this crate does not know.
+    pub fn model(&self) -> ReportedModel {
+        self.model
+    }
+
+    /// Error information 1 (byte 8), decoded by `errors`.
+    pub fn error_info_1(&self) -> u8 {
+        self.raw[8]
+    }
+
+    /// Error information 2 (byte 9), decoded by `errors`.
+    pub fn error_info_2(&self) -> u8 {
+        self.raw[9]
+    }
+
+    /// `true` when neither error byte has a flag set.
+    pub fn is_ok(&self) -> bool {
+        self.errors().is_empty()
+    }
+
+    /// Every error condition of both error bytes.
+    pub fn errors(&self) -> PrinterErrors {
+        PrinterErrors::from_buf(self.raw)
+    }
+
+    /// Installed media, `None` when there is no media or it is not known to this crate.
+    pub fn media(&self) -> Option<Media> {
+        self.media
+    }
+
+    /// Media width in mm (byte 10).
+    pub fn media_width_mm(&self) -> u8 {
+        self.raw[10]
+    }
+
+    /// Media type (byte 11).
+    pub fn media_type(&self) -> MediaType {
+        MediaType::from_code(self.raw[11])
+    }
+
+    /// Media length in mm (byte 17), 0 for continuous media.
+    pub fn media_length_mm(&self) -> u8 {
+        self.raw[17]
+    }
+
+    /// Various mode settings (byte 15).
+    pub fn mode(&self) -> u8 {
+        self.mode
+    }
+
+    /// Status type (byte 18).
+    pub fn status_type(&self) -> StatusType {
+        self.status_type
+    }
+
+    /// Phase type and number (bytes 19 to 21).
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Notification number (byte 22).
+    pub fn notification(&self) -> Notification {
+        self.notification
+    }
+
+    /// Status id (byte 14).
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Check the installed media is the `expected_media`.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The media matches
+    /// * `Err(Error::MediaMismatch)` - Other media is installed
+    /// * `Err(Error::NoMediaInstalled)` - No media, or media unknown to this crate
+    pub fn check_media(&self, expected_media: Media) -> Result<(), Error> {
+        match self.media {
+            Some(actual_media) => {
+                if actual_media == expected_media {
+                    Ok(())
+                } else {
+                    Err(Error::MediaMismatch {
+                        expected: expected_media,
+                        actual: actual_media,
+                    })
+                }
+            }
+            None => Err(Error::NoMediaInstalled),
+        }
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}, {}", self.model, self.status_type, self.phase)?;
+
+        let width = self.media_width_mm();
+        let length = self.media_length_mm();
+        match self.media_type() {
+            MediaType::NoMedia => write!(f, ", no media")?,
+            MediaType::Continuous => write!(f, ", {}mm continuous", width)?,
+            MediaType::DieCut => write!(f, ", {}x{}mm die-cut", width, length)?,
+            MediaType::Unknown(code) => write!(f, ", unknown media {:#04x}", code)?,
+        }
+
+        if self.notification != Notification::NotAvailable {
+            write!(f, ", {}", self.notification)?;
+        }
+
+        let errors = self.errors();
+        if !errors.is_empty() {
+            write!(f, ": {}", errors)?;
+        }
+        Ok(())
+    }
+}
+
+// StatusType
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StatusType {
+    ReplyToRequest,
+    Completed,
+    Error,
+    Offline,
+    Notification,
+    PhaseChange,
+    Unknown(u8),
+}
+
+impl StatusType {
+    fn from_code(code: u8) -> StatusType {
+        match code {
+            0x00 => Self::ReplyToRequest,
+            0x01 => Self::Completed,
+            0x02 => Self::Error,
+            0x04 => Self::Offline,
+            0x05 => Self::Notification,
+            0x06 => Self::PhaseChange,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for StatusType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReplyToRequest => write!(f, "ready"),
+            Self::Completed => write!(f, "printing completed"),
+            Self::Error => write!(f, "error"),
+            Self::Offline => write!(f, "offline"),
+            Self::Notification => write!(f, "notification"),
+            Self::PhaseChange => write!(f, "phase changed"),
+            Self::Unknown(code) => write!(f, "unknown status {:#04x}", code),
+        }
+    }
+}
+
+// Phase
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Phase {
+    Receiving,
+    Printing,
+    /// Phase type not documented, with its phase number
+    Waiting(u16),
+}
+
+impl Phase {
+    fn from_buf(buf: [u8; STATUS_SIZE]) -> Self {
+        match buf[19] {
+            0x00 => Self::Receiving,
+            0x01 => Self::Printing,
+            _ => Self::Waiting(u16::from_be_bytes([buf[20], buf[21]])),
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Receiving => write!(f, "receiving"),
+            Self::Printing => write!(f, "printing"),
+            Self::Waiting(n) => write!(f, "waiting ({})", n),
+        }
+    }
+}
+
+// Notification
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Notification {
+    NotAvailable,
+    CoolingStarted,
+    CoolingFinished,
+}
+
+impl Notification {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x03 => Self::CoolingStarted,
+            0x04 => Self::CoolingFinished,
+            _ => Self::NotAvailable,
+        }
+    }
+}
+
+impl fmt::Display for Notification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAvailable => write!(f, "no notification"),
+            Self::CoolingStarted => write!(f, "cooling started"),
+            Self::CoolingFinished => write!(f, "cooling finished"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DieCutType, Model};
+
+    #[test]
+    fn test_status_fields() {
+        let mut buf = [0u8; STATUS_SIZE];
+        buf[..6].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x41, 0x30]);
+        buf[8] = 0x02;
+        buf[10] = 29;
+        buf[11] = 0x0B;
+        buf[15] = 0x40;
+        buf[17] = 90;
+        buf[18] = 0x05;
+        buf[19] = 0x01;
+        buf[22] = 0x03;
+        buf[25] = 0x01;
+
+        let status = Status::from_buf(buf);
+        assert_eq!(status.head_mark(), 0x80);
+        assert_eq!(status.model(), ReportedModel::Known(Model::QL820NWB));
+        assert_eq!(status.error_info_1(), 0x02);
+        assert_eq!(status.error_info_2(), 0x00);
+        assert_eq!(status.errors(), PrinterErrors::END_OF_MEDIA);
+        assert!(!status.is_ok());
+        assert_eq!(status.media(), Some(Media::DieCut(DieCutType::DieCut29x90)));
+        assert_eq!(status.media_type(), MediaType::DieCut);
+        assert_eq!(status.mode(), 0x40);
+        assert_eq!(status.status_type(), StatusType::Notification);
+        assert_eq!(status.phase(), Phase::Printing);
+        assert_eq!(status.notification(), Notification::CoolingStarted);
+        assert_eq!(status.raw(), &buf);
+        assert_eq!(
+            status.to_string(),
+            "QL-820NWB notification, printing, 29x90mm die-cut, cooling started: end of media"
+        );
+    }
+
+    #[test]
+    fn test_unknown_model_code() {
+        let mut buf = [0u8; STATUS_SIZE];
+        buf[..6].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x99, 0x30]);
+        let status = Status::from_buf(buf);
+        assert_eq!(status.model(), ReportedModel::Unknown(0x99));
+        assert_eq!(status.model().model(), None);
+    }
+}
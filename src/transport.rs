@@ -0,0 +1,50 @@
+//! Byte transports used to talk to a printer.
+//!
+//! The raster protocol is the same regardless of how the printer is attached,
+//! so `Printer` only needs something that can send bytes and read back the
+//! 32-byte status replies. `UsbTransport` is the libusb implementation used by
+//! `Printer::new`, `TcpTransport` talks to network models on port 9100 and
+//! `UsblpTransport` goes through the Linux `usblp` character device.
+
+use std::time::Duration;
+
+use crate::error::Error;
+
+mod tcp;
+mod usb;
+#[cfg(target_os = "linux")]
+mod usblp;
+
+pub use self::{
+    tcp::{TcpTransport, RAW_PORT},
+    usb::{DeviceInfo, UsbTransport},
+};
+
+pub(crate) use self::usb::VENDOR_ID;
+
+#[cfg(target_os = "linux")]
+pub use self::usblp::UsblpTransport;
+
+/// Size of a status reply sent by the printer.
+pub const STATUS_SIZE: usize = 32;
+
+/// Connection to a printer able to carry raster commands and status replies.
+///
+/// Implementations take `&self` so a `Printer` can be used through a shared
+/// reference, the same way the USB handle is.
+pub trait Transport {
+    /// Send the whole buffer to the printer.
+    ///
+    /// Returns an error if the buffer could not be written completely.
+    fn write(&self, buf: &[u8]) -> Result<(), Error>;
+
+    /// Read a status reply into `buf`, waiting at most `timeout`.
+    ///
+    /// Returns the number of bytes received. A value other than
+    /// `STATUS_SIZE` means no complete status was available yet and the
+    /// caller is expected to poll again.
+    fn read(&self, buf: &mut [u8; STATUS_SIZE], timeout: Duration) -> Result<usize, Error>;
+
+    /// Reset the underlying connection.
+    fn reset(&self) -> Result<(), Error>;
+}
@@ -0,0 +1,176 @@
+use log::{debug, info};
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{Transport, STATUS_SIZE};
+use crate::error::Error;
+
+/// Raw port used by Brother network printers.
+pub const RAW_PORT: u16 = 9100;
+
+// Longest single wait on the socket, the interval at which `Printer` polls
+// for a status and checks for cancellation
+const POLL_STEP: Duration = Duration::from_millis(50);
+
+/// Network transport sending the raster stream to the raw TCP port.
+///
+/// Models with Ethernet or Wi-Fi (QL-720NW, QL-810W, QL-820NWB, QL-1110NWB, ...)
+/// accept exactly the same byte stream on port 9100 as on the USB bulk endpoint
+/// and answer status requests with the same 32-byte replies.
+pub struct TcpTransport {
+    stream: TcpStream,
+    // Status replies may be split over several TCP segments
+    pending: Mutex<Vec<u8>>,
+}
+
+impl TcpTransport {
+    /// Connect to a printer, e.g. `"192.168.1.20:9100"`.
+    ///
+    /// # Returns
+    /// * `Ok(TcpTransport)` - Connected socket
+    /// * `Err(Error)` - Address could not be resolved or the connection failed
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, Error> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        info!("Connected to printer at {}", stream.peer_addr()?);
+        Ok(Self::from_stream(stream))
+    }
+
+    /// Wrap an already connected socket.
+    pub fn from_stream(stream: TcpStream) -> Self {
+        TcpTransport {
+            stream,
+            pending: Mutex::new(Vec::with_capacity(STATUS_SIZE)),
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    fn write(&self, buf: &[u8]) -> Result<(), Error> {
+        (&self.stream).write_all(buf)?;
+        (&self.stream).flush()?;
+        debug!("Successfully wrote {} bytes to socket", buf.len());
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8; STATUS_SIZE], timeout: Duration) -> Result<usize, Error> {
+        let mut pending = self.pending.lock().unwrap();
+
+        // A zero duration would make the socket blocking
+        let timeout = timeout.min(POLL_STEP).max(Duration::from_millis(1));
+        self.stream.set_read_timeout(Some(timeout))?;
+
+        while pending.len() < STATUS_SIZE {
+            let mut chunk = [0u8; STATUS_SIZE];
+            let wanted = STATUS_SIZE - pending.len();
+            match (&self.stream).read(&mut chunk[..wanted]) {
+                Ok(0) => return Err(Error::DeviceOffline),
+                Ok(n) => pending.extend_from_slice(&chunk[..n]),
+                // 受信途中のステータスは次の読み出しに持ち越す
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    return Ok(pending.len());
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+
+        buf.copy_from_slice(&pending[..STATUS_SIZE]);
+        pending.clear();
+        Ok(STATUS_SIZE)
+    }
+
+    fn reset(&self) -> Result<(), Error> {
+        // Nothing to reset on a socket, just drop a partially received status
+        self.pending.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, ContinuousType, Media, Model, Printer};
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_status_over_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // 印刷機の代わりにステータス要求へ応答する
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = [0u8; 405];
+            socket.read_exact(&mut request).unwrap();
+            assert_eq!(&request[400..], &[0x1B, 0x40, 0x1B, 0x69, 0x53]);
+
+            let mut status = [0u8; STATUS_SIZE];
+            status[..6].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x41, 0x30]);
+            status[10] = 62;
+            status[11] = 0x0A;
+            status[25] = 0x01;
+            // 分割して送信しても1つのステータスとして受信できること
+            socket.write_all(&status[..10]).unwrap();
+            socket.flush().unwrap();
+            thread::sleep(Duration::from_millis(20));
+            socket.write_all(&status[10..]).unwrap();
+        });
+
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let config = Config::new(Model::QL820NWB, "serial".to_string(), media);
+        let printer = Printer::connect_tcp(addr, config).unwrap();
+        let status = printer.check_status().unwrap();
+        assert!(status.check_media(media).is_ok());
+
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_partial_status_is_kept() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let transport = TcpTransport::connect(addr).unwrap();
+        let (mut socket, _) = listener.accept().unwrap();
+
+        let status: Vec<u8> = (0..STATUS_SIZE as u8).collect();
+        socket.write_all(&status[..10]).unwrap();
+        socket.flush().unwrap();
+
+        // 待ち時間が長くても、1回の読み出しは1ポーリング分で戻る
+        let mut buf = [0u8; STATUS_SIZE];
+        let started = std::time::Instant::now();
+        assert_eq!(transport.read(&mut buf, Duration::from_secs(1)).unwrap(), 10);
+        assert!(started.elapsed() < Duration::from_millis(500));
+
+        socket.write_all(&status[10..]).unwrap();
+        assert_eq!(transport.read(&mut buf, Duration::from_secs(1)).unwrap(), STATUS_SIZE);
+        assert_eq!(&buf[..], &status[..]);
+    }
+
+    #[test]
+    fn test_silent_printer_times_out() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // 接続は受け付けるがステータスを返さない
+        let server = thread::spawn(move || {
+            let (mut socket, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let _ = socket.read_to_end(&mut request);
+        });
+
+        let media = Media::Continuous(ContinuousType::Continuous62);
+        let config = Config::new(Model::QL820NWB, "serial".to_string(), media);
+        let printer = Printer::connect_tcp(addr, config).unwrap();
+        let started = std::time::Instant::now();
+        assert!(matches!(printer.check_status(), Err(Error::ReadStatusTimeout)));
+        assert!(started.elapsed() < Duration::from_secs(3));
+
+        drop(printer);
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,299 @@
+use log::{debug, error, info, warn};
+use rusb::{Context, Device, DeviceDescriptor, DeviceHandle, Direction, TransferType, UsbContext};
+use std::time::Duration;
+
+use super::{Transport, STATUS_SIZE};
+use crate::{error::Error, media::Media, model::Model, printer::Config};
+
+// Vendoer id of Brother Industries, Ltd
+pub(crate) const VENDOR_ID: u16 = 0x04f9;
+
+#[derive(Debug, Clone, Copy)]
+#[allow(dead_code)]
+struct Endpoint {
+    config: u8,
+    iface: u8,
+    setting: u8,
+    address: u8,
+}
+
+/// Brother label printer found on the USB bus.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceInfo {
+    pub model: Model,
+    pub serial: String,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub bus_number: u8,
+    pub address: u8,
+}
+
+impl DeviceInfo {
+    /// Build a default configuration for this printer with `media` installed.
+    pub fn config(&self, media: Media) -> Config {
+        Config::new(self.model, self.serial.clone(), media)
+    }
+}
+
+/// USB bulk transport based on libusb.
+pub struct UsbTransport {
+    handle: Box<DeviceHandle<Context>>,
+    endpoint_out: Endpoint,
+    endpoint_in: Endpoint,
+}
+
+impl UsbTransport {
+    /// Open the printer with the given product id and serial number.
+    ///
+    /// The device is reset, the kernel driver is detached when needed and
+    /// the printer interface is claimed.
+    ///
+    /// # Returns
+    /// * `Ok(UsbTransport)` - Successfully opened device
+    /// * `Err(Error)` - Device not found, missing endpoint or USB error
+    pub fn open(pid: u16, serial: &str) -> Result<Self, Error> {
+        // rusb::set_log_level(rusb::LogLevel::Debug);
+        let mut context = Context::new()?;
+        match Self::open_device(&mut context, pid, serial) {
+            Ok((mut device, device_desc, handle)) => {
+                handle.reset()?;
+
+                let endpoint_in = match Self::find_endpoint(
+                    &mut device,
+                    &device_desc,
+                    Direction::In,
+                    TransferType::Bulk,
+                ) {
+                    Some(endpoint) => endpoint,
+                    None => return Err(Error::MissingEndpoint),
+                };
+
+                let endpoint_out = match Self::find_endpoint(
+                    &mut device,
+                    &device_desc,
+                    Direction::Out,
+                    TransferType::Bulk,
+                ) {
+                    Some(endpoint) => endpoint,
+                    None => return Err(Error::MissingEndpoint),
+                };
+
+                // QL-800では`has_kernel_driver`が`true`となる
+                // QL-820NWBでは`has_kernel_driver`が`false`となる
+                // `has_kernel_driver`が`true`の場合に、カーネルドライバーをデタッチしないとエラーとなる
+                //
+                handle.set_auto_detach_kernel_driver(true)?;
+                let has_kernel_driver = match handle.kernel_driver_active(0) {
+                    Ok(true) => {
+                        handle.detach_kernel_driver(0).ok();
+                        true
+                    }
+                    _ => false,
+                };
+                info!(" Kernel driver support is {}", has_kernel_driver);
+                handle.set_active_configuration(1)?;
+                handle.claim_interface(0)?;
+                handle.set_alternate_setting(0, 0)?;
+
+                Ok(UsbTransport {
+                    handle: Box::new(handle),
+                    endpoint_out,
+                    endpoint_in,
+                })
+            }
+            Err(err) => {
+                debug!("Device connection failed: {:?}", err);
+                Err(Error::DeviceOffline)
+            }
+        }
+    }
+
+    /// List every Brother label printer attached to the USB bus.
+    ///
+    /// Devices whose product id is not a known `Model`, or whose serial number
+    /// can not be read (usually a permission issue), are skipped.
+    pub fn discover() -> Result<Vec<DeviceInfo>, Error> {
+        let context = Context::new()?;
+        let devices = context.devices()?;
+
+        let mut found = Vec::new();
+        for device in devices.iter() {
+            if let Some((info, _, _)) = Self::read_device_info(&device) {
+                found.push(info);
+            }
+        }
+        Ok(found)
+    }
+
+    /// Open a Brother printer and read its descriptor strings.
+    pub(crate) fn read_device_info(
+        device: &Device<Context>,
+    ) -> Option<(DeviceInfo, DeviceDescriptor, DeviceHandle<Context>)> {
+        let device_desc = match device.device_descriptor() {
+            Ok(d) => d,
+            Err(err) => {
+                debug!("{:#?}", err);
+                return None;
+            }
+        };
+        debug!(
+            "vender_id: {:x},  product_id: {:x}",
+            device_desc.vendor_id(),
+            device_desc.product_id()
+        );
+        if device_desc.vendor_id() != VENDOR_ID {
+            return None;
+        }
+        let model = Model::from_pid(device_desc.product_id())?;
+
+        let handle = match device.open() {
+            Ok(handle) => handle,
+            Err(err) => {
+                debug!("Unable to open USB device: {:?}", err);
+                return None;
+            }
+        };
+        let timeout = Duration::from_secs(1);
+        let language = match handle.read_languages(timeout) {
+            Ok(languages) if !languages.is_empty() => languages[0],
+            Ok(_) => return None,
+            Err(err) => {
+                debug!("Cannot read device languages: {:?}", err);
+                return None;
+            }
+        };
+        let serial = match handle.read_serial_number_string(language, &device_desc, timeout) {
+            Ok(s) => s,
+            Err(err) => {
+                debug!("Cannot read device serial number: {:?}", err);
+                return None;
+            }
+        };
+        let info = DeviceInfo {
+            model,
+            serial,
+            manufacturer: handle
+                .read_manufacturer_string(language, &device_desc, timeout)
+                .ok(),
+            product: handle
+                .read_product_string(language, &device_desc, timeout)
+                .ok(),
+            bus_number: device.bus_number(),
+            address: device.address(),
+        };
+        Some((info, device_desc, handle))
+    }
+
+    fn open_device(
+        context: &mut Context,
+        pid: u16,
+        serial: &str,
+    ) -> Result<(Device<Context>, DeviceDescriptor, DeviceHandle<Context>), Error> {
+        let devices = context.devices()?;
+
+        if devices.is_empty() {
+            warn!("Unable to enumerate USB devices");
+            return Err(Error::DeviceListNotReadable);
+        }
+        for device in devices.iter() {
+            if let Some((info, device_desc, handle)) = Self::read_device_info(&device) {
+                if info.model.pid() == pid && info.serial == serial {
+                    info!("Connected to printer (serial: {})", serial);
+                    return Ok((device, device_desc, handle));
+                }
+            }
+        }
+        error!("No printer found with serial number: {}", serial);
+        Err(Error::DeviceOffline)
+    }
+
+    fn find_endpoint(
+        device: &mut Device<Context>,
+        device_desc: &DeviceDescriptor,
+        direction: Direction,
+        transfer_type: TransferType,
+    ) -> Option<Endpoint> {
+        for n in 0..device_desc.num_configurations() {
+            let config_desc = match device.config_descriptor(n) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            for interface in config_desc.interfaces() {
+                for interface_desc in interface.descriptors() {
+                    for endpoint_desc in interface_desc.endpoint_descriptors() {
+                        if endpoint_desc.direction() == direction
+                            && endpoint_desc.transfer_type() == transfer_type
+                        {
+                            return Some(Endpoint {
+                                config: config_desc.number(),
+                                iface: interface_desc.interface_number(),
+                                setting: interface_desc.setting_number(),
+                                address: endpoint_desc.address(),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
+
+impl Transport for UsbTransport {
+    fn write(&self, buf: &[u8]) -> Result<(), Error> {
+        // 動的タイムアウト計算
+        // - ベースタイムアウト: 5秒
+        // - データサイズ依存: 1MB/sの転送速度を仮定
+        // - 安全マージン: 2倍
+        let base_timeout_secs = 5;
+        let transfer_rate_bytes_per_sec = 1_000_000; // 1MB/s
+        let safety_margin = 2.0;
+
+        let data_dependent_timeout =
+            (buf.len() as f64 / transfer_rate_bytes_per_sec as f64) * safety_margin;
+        let total_timeout_secs = base_timeout_secs as f64 + data_dependent_timeout;
+
+        // 最小10秒、最大60秒の範囲でクランプ
+        let timeout_secs = total_timeout_secs.clamp(10.0, 60.0);
+        let timeout = Duration::from_secs(timeout_secs as u64);
+
+        debug!(
+            "USB transfer timeout set to {:.1}s for {} bytes",
+            timeout_secs,
+            buf.len()
+        );
+        let result = self
+            .handle
+            .write_bulk(self.endpoint_out.address, buf, timeout);
+        match result {
+            Ok(n) => {
+                if n == buf.len() {
+                    debug!(
+                        "Successfully wrote {} bytes to endpoint {:#x}",
+                        n, self.endpoint_out.address
+                    );
+                    Ok(())
+                } else {
+                    warn!(
+                        "USB write incomplete: {} of {} bytes transferred (possible timeout)",
+                        n,
+                        buf.len()
+                    );
+                    Err(Error::InvalidResponse(n))
+                }
+            }
+            Err(e) => Err(Error::UsbError(e)),
+        }
+    }
+
+    fn read(&self, buf: &mut [u8; STATUS_SIZE], timeout: Duration) -> Result<usize, Error> {
+        debug!("reading from endpoint_in {:#?}", self.endpoint_in);
+        Ok(self
+            .handle
+            .read_bulk(self.endpoint_in.address, buf, timeout)?)
+    }
+
+    fn reset(&self) -> Result<(), Error> {
+        Ok(self.handle.reset()?)
+    }
+}
@@ -0,0 +1,197 @@
+use log::{debug, info};
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::{Transport, STATUS_SIZE};
+use crate::error::Error;
+
+/// Transport writing to the Linux `usblp` character device (`/dev/usb/lpN`).
+///
+/// The kernel printer driver stays attached, so neither libusb nor the
+/// permission to detach kernel drivers is needed. Access to the device node
+/// is enough, e.g. through the `lp` group or a udev rule.
+pub struct UsblpTransport {
+    file: File,
+    // usblp may hand the status back in several reads
+    pending: Mutex<Vec<u8>>,
+}
+
+impl UsblpTransport {
+    /// Open the device node, e.g. `/dev/usb/lp0`.
+    ///
+    /// # Returns
+    /// * `Ok(UsblpTransport)` - Opened device
+    /// * `Err(Error)` - The node does not exist or is not accessible
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(path)?;
+        info!("Opened printer device {}", path.display());
+        Ok(UsblpTransport {
+            file,
+            pending: Mutex::new(Vec::with_capacity(STATUS_SIZE)),
+        })
+    }
+
+    fn poll_readable(&self, timeout: Duration) -> Result<bool, Error> {
+        let mut fds = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let millis = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        loop {
+            // SAFETY: `fds` is a valid pollfd for the lifetime of the call.
+            let n = unsafe { libc::poll(&mut fds, 1, millis) };
+            if n >= 0 {
+                return Ok(n > 0);
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != ErrorKind::Interrupted {
+                return Err(Error::IoError(err));
+            }
+        }
+    }
+}
+
+impl Transport for UsblpTransport {
+    fn write(&self, buf: &[u8]) -> Result<(), Error> {
+        (&self.file).write_all(buf)?;
+        (&self.file).flush()?;
+        debug!("Successfully wrote {} bytes to device", buf.len());
+        Ok(())
+    }
+
+    fn read(&self, buf: &mut [u8; STATUS_SIZE], timeout: Duration) -> Result<usize, Error> {
+        let mut pending = self.pending.lock().unwrap();
+        let deadline = Instant::now() + timeout;
+
+        while pending.len() < STATUS_SIZE {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            // 受信途中のステータスは次の読み出しに持ち越す
+            if !self.poll_readable(remaining)? {
+                return Ok(pending.len());
+            }
+            let mut chunk = [0u8; STATUS_SIZE];
+            let wanted = STATUS_SIZE - pending.len();
+            match (&self.file).read(&mut chunk[..wanted]) {
+                // usblp returns 0 when the printer has nothing to report
+                Ok(0) => return Ok(0),
+                Ok(n) => pending.extend_from_slice(&chunk[..n]),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::IoError(e)),
+            }
+        }
+
+        buf.copy_from_slice(&pending[..STATUS_SIZE]);
+        pending.clear();
+        Ok(STATUS_SIZE)
+    }
+
+    fn reset(&self) -> Result<(), Error> {
+        // Just drop a partially received status
+        self.pending.lock().unwrap().clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Config, DieCutType, Media, Model, Printer};
+    use std::ffi::CStr;
+    use std::os::unix::io::FromRawFd;
+    use std::thread;
+
+    // 疑似端末のマスター側を開き、スレーブ側のパスを返す
+    fn open_pty() -> (File, String) {
+        unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let mut name = [0 as libc::c_char; 64];
+            assert_eq!(libc::ptsname_r(master, name.as_mut_ptr(), name.len()), 0);
+            let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_string();
+
+            // エコーや改行変換でデータが変わらないようにrawモードにする
+            let mut termios = std::mem::zeroed::<libc::termios>();
+            assert_eq!(libc::tcgetattr(master, &mut termios), 0);
+            libc::cfmakeraw(&mut termios);
+            assert_eq!(libc::tcsetattr(master, libc::TCSANOW, &termios), 0);
+
+            (File::from_raw_fd(master), path)
+        }
+    }
+
+    #[test]
+    fn test_status_over_character_device() {
+        let (mut master, path) = open_pty();
+
+        let media = Media::DieCut(DieCutType::DieCut29x90);
+        let config = Config::new(Model::QL800, "serial".to_string(), media);
+        let printer = Printer::open_usblp(&path, config).unwrap();
+
+        let device = thread::spawn(move || {
+            let mut request = [0u8; 405];
+            master.read_exact(&mut request).unwrap();
+            assert_eq!(&request[400..], &[0x1B, 0x40, 0x1B, 0x69, 0x53]);
+
+            let mut status = [0u8; STATUS_SIZE];
+            status[..6].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x38, 0x30]);
+            status[10] = 29;
+            status[11] = 0x0B;
+            status[17] = 90;
+            master.write_all(&status).unwrap();
+            master
+        });
+
+        let status = printer.check_status().unwrap();
+        assert!(status.check_media(media).is_ok());
+
+        device.join().unwrap();
+    }
+
+    #[test]
+    fn test_partial_status_within_timeout() {
+        let (mut master, path) = open_pty();
+        let transport = UsblpTransport::open(&path).unwrap();
+
+        // 待っている途中で一部だけ届いても、待ち時間は延びない
+        let writer = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            master.write_all(&[0x80; 10]).unwrap();
+            master
+        });
+        let mut buf = [0u8; STATUS_SIZE];
+        let started = std::time::Instant::now();
+        assert_eq!(transport.read(&mut buf, Duration::from_millis(300)).unwrap(), 10);
+        assert!(started.elapsed() < Duration::from_millis(450), "{:?}", started.elapsed());
+        let mut master = writer.join().unwrap();
+
+        master.write_all(&[0x00; STATUS_SIZE - 10]).unwrap();
+        assert_eq!(transport.read(&mut buf, Duration::from_millis(300)).unwrap(), STATUS_SIZE);
+        assert_eq!(&buf[..10], &[0x80; 10]);
+    }
+
+    #[test]
+    fn test_silent_device_times_out() {
+        let (_master, path) = open_pty();
+
+        let media = Media::DieCut(DieCutType::DieCut29x90);
+        let config = Config::new(Model::QL800, "serial".to_string(), media);
+        let printer = Printer::open_usblp(&path, config).unwrap();
+
+        let started = std::time::Instant::now();
+        assert!(matches!(printer.check_status(), Err(Error::ReadStatusTimeout)));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+}